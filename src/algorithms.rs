@@ -0,0 +1,365 @@
+#[cfg(any(feature = "png", feature = "jpeg"))]
+use imgref::ImgRef;
+use imgref::{ImgRefMut, ImgVec};
+
+use rgb::alt::BGRA8;
+use rgb::ColorComponentMap;
+
+use crate::cli::FlipMode;
+use crate::types::ClipRect;
+
+/// Darken the bottom `shadow_height` rows of `monitor`, fading from nothing at the top of the band to `max_opacity` black at the bottom.
+pub fn draw_bottom_shadow(
+    img: &mut ImgRefMut<BGRA8>,
+    monitor: ClipRect,
+    shadow_height: usize,
+    max_opacity: f32,
+) {
+    let ClipRect {
+        x,
+        y,
+        width,
+        height,
+    } = monitor;
+    let shadow_height = shadow_height.min(height);
+    if shadow_height == 0 {
+        return;
+    }
+
+    let mut band = img.sub_image_mut(x, y + height - shadow_height, width, shadow_height);
+    for (i, row) in band.rows_mut().enumerate() {
+        let keep = 1.0 - max_opacity * (i + 1) as f32 / shadow_height as f32;
+        for px in row.iter_mut() {
+            *px = px.map_c(|c| (f32::from(c) * keep) as u8);
+        }
+    }
+}
+
+/// Copy each of `rects`, to put back later with `restore_rects`.
+pub fn save_rects(img: &ImgRefMut<BGRA8>, rects: &[ClipRect]) -> Vec<ImgVec<BGRA8>> {
+    rects
+        .iter()
+        .map(|r| {
+            let buf = img
+                .sub_image(r.x, r.y, r.width, r.height)
+                .rows()
+                .flat_map(|row| row.iter().copied())
+                .collect();
+            ImgVec::new(buf, r.width, r.height)
+        })
+        .collect()
+}
+
+/// Put back the pixels copied by `save_rects` with the same `rects`.
+pub fn restore_rects(img: &mut ImgRefMut<BGRA8>, rects: &[ClipRect], saved: &[ImgVec<BGRA8>]) {
+    for (r, copy) in rects.iter().zip(saved) {
+        let mut view = img.sub_image_mut(r.x, r.y, r.width, r.height);
+        for (dst, src) in view.rows_mut().zip(copy.rows()) {
+            dst.copy_from_slice(src);
+        }
+    }
+}
+
+/// Copy the top `rows` rows of each monitor, to put back later with `restore_top_rows`.
+pub fn save_top_rows(
+    img: &ImgRefMut<BGRA8>,
+    monitors: &[ClipRect],
+    rows: usize,
+) -> Vec<ImgVec<BGRA8>> {
+    let bars: Vec<_> = monitors
+        .iter()
+        .map(|m| ClipRect::new(m.x, m.y, m.width, rows.min(m.height)))
+        .collect();
+    save_rects(img, &bars)
+}
+
+/// Put back the rows copied by `save_top_rows` with the same `monitors`.
+pub fn restore_top_rows(
+    img: &mut ImgRefMut<BGRA8>,
+    monitors: &[ClipRect],
+    saved: &[ImgVec<BGRA8>],
+) {
+    let bars: Vec<_> = monitors
+        .iter()
+        .zip(saved)
+        .map(|(m, bar)| ClipRect::new(m.x, m.y, bar.width(), bar.height()))
+        .collect();
+    restore_rects(img, &bars, saved);
+}
+
+/// Draw a `thickness` pixel wide border just inside `monitor`.
+pub fn draw_border(img: &mut ImgRefMut<BGRA8>, monitor: ClipRect, thickness: usize, color: BGRA8) {
+    let (w, h) = (monitor.width, monitor.height);
+    let t = thickness.min(w).min(h);
+
+    let mut view = img.sub_image_mut(monitor.x, monitor.y, w, h);
+    for (i, row) in view.rows_mut().enumerate() {
+        if i < t || i >= h - t {
+            row.fill(color);
+        } else {
+            row[..t].fill(color);
+            row[w - t..].fill(color);
+        }
+    }
+}
+
+/// Draw `cols - 1` vertical and `rows - 1` horizontal 1 pixel lines splitting `img`
+/// into even cells, mixing `color` over the image at `opacity`.
+pub fn draw_grid(img: &mut ImgRefMut<BGRA8>, cols: usize, rows: usize, color: BGRA8, opacity: f32) {
+    let (w, h) = (img.width(), img.height());
+    let mix = |px: &mut BGRA8| {
+        let blend = |bot: u8, top: u8| {
+            (f32::from(bot) + (f32::from(top) - f32::from(bot)) * opacity).round() as u8
+        };
+        *px = BGRA8 {
+            b: blend(px.b, color.b),
+            g: blend(px.g, color.g),
+            r: blend(px.r, color.r),
+            a: px.a,
+        };
+    };
+
+    let xs: Vec<usize> = (1..cols).map(|i| i * w / cols).collect();
+    for (y, row) in img.rows_mut().enumerate() {
+        if (1..rows).any(|j| j * h / rows == y) {
+            row.iter_mut().for_each(&mix);
+        } else {
+            xs.iter().for_each(|&x| mix(&mut row[x]));
+        }
+    }
+}
+
+/// Fail if an `icon_w`x`icon_h` icon doesn't fit on a `monitor_w`x`monitor_h` monitor.
+pub fn check_icon_fits(
+    icon_w: usize,
+    icon_h: usize,
+    monitor_w: usize,
+    monitor_h: usize,
+) -> Result<(), String> {
+    if icon_w > monitor_w || icon_h > monitor_h {
+        return Err(format!(
+            "the {}x{} icon is larger than the {}x{} monitor",
+            icon_w, icon_h, monitor_w, monitor_h
+        ));
+    }
+    Ok(())
+}
+
+/// Mirror `img` in place, `Horizontal` swaps left and right, `Vertical` top and bottom.
+pub fn flip(img: &mut ImgRefMut<BGRA8>, mode: FlipMode) {
+    let (w, h) = (img.width(), img.height());
+    if mode != FlipMode::Vertical {
+        img.rows_mut().for_each(<[BGRA8]>::reverse);
+    }
+    if mode != FlipMode::Horizontal {
+        for y in 0..h / 2 {
+            for x in 0..w {
+                let top = img[(x, y)];
+                img[(x, y)] = img[(x, h - 1 - y)];
+                img[(x, h - 1 - y)] = top;
+            }
+        }
+    }
+}
+
+/// Draw a `width` pixel ring of `color` around the opaque part of `icon` placed at `x`,`y`.
+///
+/// The icon's alpha mask is dilated by a disc of radius `width` and the mask itself
+/// is subtracted, so the icon is never drawn over. Nothing is drawn outside of `clip`.
+#[cfg(any(feature = "png", feature = "jpeg"))]
+pub fn draw_icon_outline(
+    img: &mut ImgRefMut<BGRA8>,
+    icon: ImgRef<BGRA8>,
+    x: usize,
+    y: usize,
+    width: usize,
+    color: BGRA8,
+    clip: Option<ClipRect>,
+) {
+    let (iw, ih) = (icon.width() as isize, icon.height() as isize);
+    let r = width as isize;
+    let opaque = |ix: isize, iy: isize| {
+        (0..iw).contains(&ix) && (0..ih).contains(&iy) && icon[(ix as usize, iy as usize)].a > 127
+    };
+    let disc: Vec<(isize, isize)> = (-r..=r)
+        .flat_map(|dy| (-r..=r).map(move |dx| (dx, dy)))
+        .filter(|&(dx, dy)| dx * dx + dy * dy <= r * r)
+        .collect();
+
+    let bounds = ClipRect::new(0, 0, img.width(), img.height());
+    let Some(clip) = clip.map_or(Some(bounds), |c| c.intersect(&bounds)) else {
+        return;
+    };
+    let left = (x as isize - r).max(clip.x as isize);
+    let up = (y as isize - r).max(clip.y as isize);
+    let right = (x as isize + iw + r).min((clip.x + clip.width) as isize);
+    let down = (y as isize + ih + r).min((clip.y + clip.height) as isize);
+
+    for py in up..down {
+        for px in left..right {
+            let (ix, iy) = (px - x as isize, py - y as isize);
+            if !opaque(ix, iy) && disc.iter().any(|&(dx, dy)| opaque(ix + dx, iy + dy)) {
+                img[(px as usize, py as usize)] = color;
+            }
+        }
+    }
+}
+
+/// Add `pixels` of transparent padding on every side of `icon`.
+/// Formats without alpha are padded with black.
+#[cfg(any(feature = "png", feature = "jpeg"))]
+pub fn pad_icon(icon: &imagefmt::Image<u8>, pixels: usize) -> imagefmt::Image<u8> {
+    use imagefmt::ColFmt::*;
+
+    let channels = match icon.fmt {
+        Y => 1,
+        YA | AY => 2,
+        RGB | BGR => 3,
+        _ => 4,
+    };
+    let (w, h) = (icon.w + 2 * pixels, icon.h + 2 * pixels);
+    let row = icon.w * channels;
+
+    let mut buf = vec![0; w * h * channels];
+    for (y, src) in icon.buf.chunks_exact(row).enumerate() {
+        let start = ((y + pixels) * w + pixels) * channels;
+        buf[start..start + row].copy_from_slice(src);
+    }
+
+    imagefmt::Image {
+        w,
+        h,
+        fmt: icon.fmt,
+        buf,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: BGRA8 = BGRA8 {
+        b: 0,
+        g: 0,
+        r: 0,
+        a: 0,
+    };
+    const WHITE: BGRA8 = BGRA8 {
+        b: 255,
+        g: 255,
+        r: 255,
+        a: 255,
+    };
+
+    #[test]
+    fn border() {
+        let mut data = vec![BLACK; 4 * 4];
+        let mut img = ImgRefMut::new(&mut data, 4, 4);
+        draw_border(&mut img, ClipRect::new(1, 1, 3, 3), 1, WHITE);
+        #[rustfmt::skip]
+        assert_eq!(
+            data,
+            [
+                BLACK, BLACK, BLACK, BLACK,
+                BLACK, WHITE, WHITE, WHITE,
+                BLACK, WHITE, BLACK, WHITE,
+                BLACK, WHITE, WHITE, WHITE,
+            ]
+        );
+    }
+
+    #[test]
+    fn grid() {
+        let mut data = vec![BLACK; 4 * 4];
+        let mut img = ImgRefMut::new(&mut data, 4, 4);
+        draw_grid(&mut img, 2, 2, WHITE, 1.0);
+        #[rustfmt::skip]
+        assert_eq!(
+            data,
+            [
+                BLACK, BLACK, WHITE, BLACK,
+                BLACK, BLACK, WHITE, BLACK,
+                WHITE, WHITE, WHITE, WHITE,
+                BLACK, BLACK, WHITE, BLACK,
+            ]
+            .map(|px| BGRA8 { a: 0, ..px })
+        );
+    }
+
+    #[test]
+    fn top_rows() {
+        let mut data = vec![BLACK; 4 * 3];
+        let mut img = ImgRefMut::new(&mut data, 4, 3);
+        let monitors = [ClipRect::new(0, 0, 2, 3), ClipRect::new(2, 0, 2, 3)];
+        let saved = save_top_rows(&img, &monitors, 1);
+        img.pixels_mut().for_each(|px| *px = WHITE);
+        restore_top_rows(&mut img, &monitors, &saved);
+        assert_eq!(data[..4], [BLACK; 4]);
+        assert_eq!(data[4..], [WHITE; 8]);
+    }
+
+    #[test]
+    fn icon_fits() {
+        assert!(check_icon_fits(100, 100, 1920, 1080).is_ok());
+        assert!(check_icon_fits(1920, 1080, 1920, 1080).is_ok());
+        assert!(check_icon_fits(1921, 10, 1920, 1080).is_err());
+        assert!(check_icon_fits(10, 1081, 1920, 1080).is_err());
+    }
+
+    #[test]
+    fn flip() {
+        let mut data = vec![BLACK, WHITE, BLACK, BLACK];
+        let mut img = ImgRefMut::new(&mut data, 2, 2);
+        super::flip(&mut img, FlipMode::Horizontal);
+        assert_eq!(data, [WHITE, BLACK, BLACK, BLACK]);
+
+        let mut img = ImgRefMut::new(&mut data, 2, 2);
+        super::flip(&mut img, FlipMode::Vertical);
+        assert_eq!(data, [BLACK, BLACK, WHITE, BLACK]);
+
+        let mut img = ImgRefMut::new(&mut data, 2, 2);
+        super::flip(&mut img, FlipMode::Both);
+        assert_eq!(data, [BLACK, WHITE, BLACK, BLACK]);
+
+        // only the sub-image is touched
+        let mut data = vec![BLACK, WHITE, WHITE, BLACK, BLACK, BLACK];
+        let mut img = ImgRefMut::new(&mut data, 3, 2);
+        super::flip(&mut img.sub_image_mut(0, 0, 2, 2), FlipMode::Horizontal);
+        assert_eq!(data, [WHITE, BLACK, WHITE, BLACK, BLACK, BLACK]);
+    }
+
+    #[test]
+    #[cfg(any(feature = "png", feature = "jpeg"))]
+    fn outline() {
+        let icon = [WHITE];
+        let mut data = vec![BLACK; 5 * 5];
+        let mut img = ImgRefMut::new(&mut data, 5, 5);
+        draw_icon_outline(&mut img, ImgRef::new(&icon, 1, 1), 2, 2, 1, WHITE, None);
+        #[rustfmt::skip]
+        assert_eq!(
+            data,
+            [
+                BLACK, BLACK, BLACK, BLACK, BLACK,
+                BLACK, BLACK, WHITE, BLACK, BLACK,
+                BLACK, WHITE, BLACK, WHITE, BLACK,
+                BLACK, BLACK, WHITE, BLACK, BLACK,
+                BLACK, BLACK, BLACK, BLACK, BLACK,
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(any(feature = "png", feature = "jpeg"))]
+    fn pad() {
+        let icon = imagefmt::Image {
+            w: 1,
+            h: 1,
+            fmt: imagefmt::ColFmt::BGRA,
+            buf: vec![255; 4],
+        };
+        let padded = pad_icon(&icon, 1);
+        assert_eq!((padded.w, padded.h), (3, 3));
+        assert_eq!(padded.buf.iter().filter(|&&b| b == 255).count(), 4);
+        assert_eq!(padded.buf[16..20], [255; 4]);
+    }
+}
@@ -1,10 +1,13 @@
 use std::num::NonZeroUsize;
 
-use imgref::ImgRefMut;
+use imgref::{ImgRefMut, ImgVec};
 
 use rgb::alt::BGRA8;
 use rgb::ComponentSlice;
 
+#[cfg(feature = "threads")]
+use rayon::prelude::*;
+
 #[cfg(not(feature = "threads"))]
 use stackblur_iter::blur_srgb;
 #[cfg(feature = "threads")]
@@ -16,9 +19,122 @@ pub trait Blur {
 
 impl Blur for ImgRefMut<'_, BGRA8> {
     fn blur(&mut self, radius: NonZeroUsize) {
-        let (w, h) = (self.width(), self.height());
+        let (w, h, stride) = (self.width(), self.height(), self.stride());
         let buf = unsafe { self.buf_mut().as_mut_slice().align_to_mut::<u32>().1 };
-        let mut img = ImgRefMut::new(buf, w, h);
+        let mut img = ImgRefMut::new_stride(buf, w, h, stride);
         blur_srgb(&mut img, radius.get());
     }
 }
+
+/// Replace the alpha of `img` with `alpha`, one value per pixel row by row,
+/// blurred on its own with `radius`. The colors are left alone.
+pub fn blur_alpha(img: &mut ImgRefMut<BGRA8>, alpha: &[u8], radius: NonZeroUsize) {
+    let plane = alpha
+        .iter()
+        .map(|&a| BGRA8 {
+            b: a,
+            g: a,
+            r: a,
+            a,
+        })
+        .collect();
+    let mut plane = ImgVec::new(plane, img.width(), img.height());
+    plane.as_mut().blur(radius);
+    for (px, blurred) in img.pixels_mut().zip(plane.pixels()) {
+        px.a = blurred.a;
+    }
+}
+
+/// Gaussian blur with standard deviation `sigma`, the kernel reaches `ceil(3 * sigma)` pixels out.
+pub fn blur_gaussian_sigma(img: &mut ImgRefMut<BGRA8>, sigma: f32) {
+    let radius = (3.0 * sigma).ceil() as usize;
+    if radius == 0 || img.width() == 0 || img.height() == 0 {
+        return;
+    }
+
+    let weights: Vec<f32> = (0..=2 * radius)
+        .map(|i| {
+            let d = i as f32 - radius as f32;
+            (-d * d / (2.0 * sigma * sigma)).exp()
+        })
+        .collect();
+    let total: f32 = weights.iter().sum();
+    let kernel: Vec<f32> = weights.iter().map(|w| w / total).collect();
+
+    convolve_1d(img, &kernel, true);
+    convolve_1d(img, &kernel, false);
+}
+
+/// Convolve each row, or each column if not `horizontal`, with the odd-length `kernel`.
+/// Pixels past the edges repeat the edge.
+fn convolve_1d(img: &mut ImgRefMut<BGRA8>, kernel: &[f32], horizontal: bool) {
+    let (w, h) = (img.width(), img.height());
+    let src: Vec<BGRA8> = img.rows().flatten().copied().collect();
+    let radius = kernel.len() / 2;
+
+    let sample = |x: usize, y: usize| {
+        let mut acc = [0.0_f32; 4];
+        for (i, k) in kernel.iter().enumerate() {
+            let px = if horizontal {
+                src[y * w + (x + i).saturating_sub(radius).min(w - 1)]
+            } else {
+                src[(y + i).saturating_sub(radius).min(h - 1) * w + x]
+            };
+            for (a, c) in acc.iter_mut().zip([px.b, px.g, px.r, px.a]) {
+                *a += k * f32::from(c);
+            }
+        }
+        let [b, g, r, a] = acc.map(|c| c.round().clamp(0.0, 255.0) as u8);
+        BGRA8 { b, g, r, a }
+    };
+    let process = |(y, row): (usize, &mut [BGRA8])| {
+        for (x, px) in row.iter_mut().enumerate() {
+            *px = sample(x, y);
+        }
+    };
+
+    #[cfg(not(feature = "threads"))]
+    img.rows_mut().enumerate().for_each(process);
+
+    #[cfg(feature = "threads")]
+    img.rows_mut().enumerate().par_bridge().for_each(process);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gaussian() {
+        let px = |v| BGRA8 {
+            b: v,
+            g: v,
+            r: v,
+            a: 255,
+        };
+        let mut data = [px(0), px(0), px(0), px(255), px(0), px(0), px(0)];
+        blur_gaussian_sigma(&mut ImgRefMut::new(&mut data, 7, 1), 1.0);
+        let values = data.map(|p| p.r);
+        assert_eq!(values, [1, 14, 62, 102, 62, 14, 1]);
+        assert!(data.iter().all(|p| p.a == 255));
+    }
+
+    #[test]
+    fn alpha() {
+        let alpha = [0, 0, 0, 0, 255, 255, 255, 255];
+        let mut data = alpha.map(|a| BGRA8 {
+            b: 10,
+            g: 20,
+            r: 30,
+            a: 255,
+        });
+        blur_alpha(
+            &mut ImgRefMut::new(&mut data, 8, 1),
+            &alpha,
+            NonZeroUsize::new(1).unwrap(),
+        );
+        assert_eq!(data[0].a, 0);
+        assert!(data[3].a > 0 && data[3].a < data[4].a && data[4].a < 255);
+        assert!(data.iter().all(|p| (p.b, p.g, p.r) == (10, 20, 30)));
+    }
+}
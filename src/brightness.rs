@@ -8,36 +8,57 @@ use rayon::prelude::*;
 use rgb::alt::BGRA8;
 use rgb::ColorComponentMap;
 
+use crate::cli::Channel;
+
 pub trait BrightnessAdj {
-    fn brighten(&mut self, amt: NonZeroU8);
-    fn darken(&mut self, amt: NonZeroU8);
+    fn brighten(&mut self, amt: NonZeroU8, channel: Channel);
+    fn darken(&mut self, amt: NonZeroU8, channel: Channel);
+}
+
+/// Apply `f` to `channel` of `pixel`, alpha is left alone.
+fn adjust(pixel: BGRA8, channel: Channel, f: impl Fn(u8) -> u8) -> BGRA8 {
+    match channel {
+        Channel::All => pixel.map_c(f),
+        Channel::R => BGRA8 {
+            r: f(pixel.r),
+            ..pixel
+        },
+        Channel::G => BGRA8 {
+            g: f(pixel.g),
+            ..pixel
+        },
+        Channel::B => BGRA8 {
+            b: f(pixel.b),
+            ..pixel
+        },
+    }
 }
 
 impl BrightnessAdj for ImgRefMut<'_, BGRA8> {
-    fn brighten(&mut self, amt: NonZeroU8) {
+    fn brighten(&mut self, amt: NonZeroU8, channel: Channel) {
         #[cfg(not(feature = "threads"))]
         for pixel in self.pixels_mut() {
-            *pixel = pixel.map_c(|c| c.saturating_add(amt.get()));
+            *pixel = adjust(*pixel, channel, |c| c.saturating_add(amt.get()));
         }
 
         #[cfg(feature = "threads")]
         self.rows_mut().par_bridge().for_each(|row| {
             for pixel in row.iter_mut() {
-                *pixel = pixel.map_c(|c| c.saturating_add(amt.get()));
+                *pixel = adjust(*pixel, channel, |c| c.saturating_add(amt.get()));
             }
         });
     }
 
-    fn darken(&mut self, amt: NonZeroU8) {
+    fn darken(&mut self, amt: NonZeroU8, channel: Channel) {
         #[cfg(not(feature = "threads"))]
         for pixel in self.pixels_mut() {
-            *pixel = pixel.map_c(|c| c.saturating_sub(amt.get()));
+            *pixel = adjust(*pixel, channel, |c| c.saturating_sub(amt.get()));
         }
 
         #[cfg(feature = "threads")]
         self.rows_mut().par_bridge().for_each(|row| {
             for pixel in row.iter_mut() {
-                *pixel = pixel.map_c(|c| c.saturating_sub(amt.get()));
+                *pixel = adjust(*pixel, channel, |c| c.saturating_sub(amt.get()));
             }
         });
     }
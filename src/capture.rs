@@ -0,0 +1,341 @@
+use std::env;
+use std::io::ErrorKind::WouldBlock;
+use std::io::{self, BufRead, Read};
+use std::process::{Command, Stdio};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use imgref::ImgVec;
+
+use rgb::alt::BGRA8;
+use rgb::{ComponentBytes, FromSlice};
+
+use scrap::Display;
+
+use xcb::{x, Connection};
+
+use crate::cli::{ScreenshotMethod, StdinFormat};
+
+/// A way of getting a screenshot of every display.
+pub trait Capturer {
+    fn capture(&mut self) -> io::Result<ImgVec<BGRA8>>;
+}
+
+/// The capturer for `--screenshot-method`.
+pub fn capturer(method: ScreenshotMethod, skip_frames: usize) -> Box<dyn Capturer> {
+    match method {
+        ScreenshotMethod::Scrap => Box::new(Scrap { skip_frames }),
+        ScreenshotMethod::Ximagesrc => Box::new(XImage),
+        ScreenshotMethod::Ffmpeg => Box::new(Ffmpeg),
+    }
+}
+
+/// Capture each display through `scrap`, see `screenshot`.
+pub struct Scrap {
+    pub skip_frames: usize,
+}
+
+impl Capturer for Scrap {
+    fn capture(&mut self) -> io::Result<ImgVec<BGRA8>> {
+        screenshot(self.skip_frames)
+    }
+}
+
+/// Grab the root window with a single `GetImage` request.
+pub struct XImage;
+
+impl Capturer for XImage {
+    fn capture(&mut self) -> io::Result<ImgVec<BGRA8>> {
+        timer_start!(get_image);
+        let (conn, screen_num) = Connection::connect(None).map_err(io::Error::other)?;
+        let screen = conn
+            .get_setup()
+            .roots()
+            .nth(screen_num as usize)
+            .ok_or_else(|| io::Error::other("X server has no such screen"))?;
+        let (w, h) = (screen.width_in_pixels(), screen.height_in_pixels());
+
+        let cookie = conn.send_request(&x::GetImage {
+            format: x::ImageFormat::ZPixmap,
+            drawable: x::Drawable::Window(screen.root()),
+            x: 0,
+            y: 0,
+            width: w,
+            height: h,
+            plane_mask: u32::MAX,
+        });
+        let reply = conn.wait_for_reply(cookie).map_err(io::Error::other)?;
+        // depth 24 and 32 both come as 4 bytes per pixel, BGRX on little endian servers
+        if reply.depth() != 24 && reply.depth() != 32 {
+            return Err(io::Error::other(format!(
+                "Unsupported root window depth: {}",
+                reply.depth()
+            )));
+        }
+
+        let buf = reply
+            .data()
+            .as_bgra()
+            .iter()
+            .map(|p| BGRA8 { a: 255, ..*p })
+            .collect();
+        timer_time!("Capturing screenshot with GetImage", get_image);
+        Ok(ImgVec::new(buf, usize::from(w), usize::from(h)))
+    }
+}
+
+/// Shell out to `ffmpeg -f x11grab` for a single frame.
+pub struct Ffmpeg;
+
+impl Capturer for Ffmpeg {
+    fn capture(&mut self) -> io::Result<ImgVec<BGRA8>> {
+        timer_start!(ffmpeg);
+        let (w, h) = screen_size()?;
+        let display = env::var_os("DISPLAY").unwrap_or_else(|| ":0".into());
+
+        let out = Command::new("ffmpeg")
+            .args(["-loglevel", "error", "-f", "x11grab", "-video_size"])
+            .arg(format!("{}x{}", w, h))
+            .arg("-i")
+            .arg(display)
+            .args(["-frames:v", "1", "-f", "rawvideo", "-pix_fmt", "bgra", "-"])
+            .stdin(Stdio::null())
+            .output()?;
+        if !out.status.success() {
+            return Err(io::Error::other(format!(
+                "ffmpeg failed: {}",
+                String::from_utf8_lossy(&out.stderr).trim()
+            )));
+        }
+
+        let frame = read_raw(&out.stdout[..], w, h)?;
+        timer_time!("Capturing screenshot with ffmpeg", ffmpeg);
+        Ok(frame)
+    }
+}
+
+/// Size of the bounding box around all displays.
+pub fn screen_size() -> io::Result<(usize, usize)> {
+    let mut max_height: usize = 0;
+    let mut max_width: usize = 0;
+    for disp in Display::all()? {
+        if disp.bottom() as usize > max_height {
+            max_height = disp.bottom() as usize;
+        }
+        if disp.right() as usize > max_width {
+            max_width = disp.right() as usize;
+        }
+    }
+    Ok((max_width, max_height))
+}
+
+/// Size of the primary display.
+pub fn primary_size() -> io::Result<(usize, usize)> {
+    let disp = Display::primary()?;
+    Ok((disp.width(), disp.height()))
+}
+
+/// Where the mouse pointer is, relative to the top-left of the whole screen.
+pub fn cursor_position() -> io::Result<(usize, usize)> {
+    let (conn, screen_num) = Connection::connect(None).map_err(io::Error::other)?;
+    let screen = conn
+        .get_setup()
+        .roots()
+        .nth(screen_num as usize)
+        .ok_or_else(|| io::Error::other("X server has no such screen"))?;
+    let cookie = conn.send_request(&x::QueryPointer {
+        window: screen.root(),
+    });
+    let reply = conn.wait_for_reply(cookie).map_err(io::Error::other)?;
+    Ok((
+        reply.root_x().max(0) as usize,
+        reply.root_y().max(0) as usize,
+    ))
+}
+
+/// Capture every display into one buffer spanning all of them.
+///
+/// The first `skip_frames` frames from each display are thrown away,
+/// some drivers hand out black frames until they've warmed up.
+pub fn screenshot(skip_frames: usize) -> io::Result<ImgVec<BGRA8>> {
+    // setup scrap
+    timer_start!(scrap);
+
+    let (max_width, max_height) = screen_size()?;
+    let mut multimon_buffer = vec![BGRA8::default(); max_width * max_height];
+
+    for (i, disp) in Display::all()?.into_iter().enumerate() {
+        let x_offset = disp.left() as usize;
+        let y_offset = disp.top() as usize;
+        let mut capture = scrap::Capturer::new(disp)?;
+
+        let (w, h) = (capture.width(), capture.height());
+        timer_time!("Setting up scrap", scrap);
+
+        // take the screenshot
+        timer_start!(screenshot);
+        let mut skip = skip_frames;
+        let mut buffer = loop {
+            match capture.frame() {
+                Ok(_) if skip > 0 => skip -= 1,
+                Ok(buf) => break buf,
+                Err(e) if e.kind() == WouldBlock => sleep(Duration::from_millis(33)),
+                Err(e) => return Err(e),
+            }
+        };
+        timer_time!(format!("Capturing screenshot on display {}", i), screenshot);
+
+        // copy into place
+        let buf_bgra = buffer.as_bgra_mut();
+        for y in 0..h {
+            let src_start = w * y;
+            let src_end = src_start + w;
+            let dst_start = (y + y_offset) * max_width + x_offset;
+            let dst_end = dst_start + w;
+
+            multimon_buffer[dst_start..dst_end].copy_from_slice(&buf_bgra[src_start..src_end]);
+        }
+    }
+
+    Ok(ImgVec::new(multimon_buffer, max_width, max_height))
+}
+
+/// Read a `width`x`height` image of raw BGRA bytes.
+pub fn read_raw<R: Read>(mut reader: R, width: usize, height: usize) -> io::Result<ImgVec<BGRA8>> {
+    let mut buf = vec![BGRA8::default(); width * height];
+    reader.read_exact(buf.as_bytes_mut())?;
+    Ok(ImgVec::new(buf, width, height))
+}
+
+/// Read an image in `format` from `reader`, raw formats are `width`x`height`.
+/// Images of more than `max_pixels` pixels are refused before anything is allocated.
+pub fn read_stdin_image<R: BufRead>(
+    mut reader: R,
+    format: StdinFormat,
+    width: usize,
+    height: usize,
+    max_pixels: usize,
+) -> io::Result<ImgVec<BGRA8>> {
+    let (width, height, bpp) = match format {
+        StdinFormat::Bgra | StdinFormat::Rgba => (width, height, 4),
+        StdinFormat::Rgb => (width, height, 3),
+        StdinFormat::Ppm => {
+            let (w, h) = read_ppm_header(&mut reader)?;
+            (w, h, 3)
+        }
+    };
+
+    let len = width
+        .checked_mul(height)
+        .filter(|&pixels| pixels <= max_pixels)
+        .and_then(|pixels| pixels.checked_mul(bpp))
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}x{} is too large for the screen", width, height),
+            )
+        })?;
+    if format == StdinFormat::Bgra {
+        return read_raw(reader, width, height);
+    }
+
+    let mut bytes = vec![0; len];
+    reader.read_exact(&mut bytes)?;
+    let buf = bytes
+        .chunks_exact(bpp)
+        .map(|px| BGRA8 {
+            b: px[2],
+            g: px[1],
+            r: px[0],
+            a: px.get(3).copied().unwrap_or(255),
+        })
+        .collect();
+    Ok(ImgVec::new(buf, width, height))
+}
+
+/// Width and height from a binary PPM (P6) header, leaving `reader` at the pixel data.
+/// Only 8-bit images are supported.
+fn read_ppm_header<R: BufRead>(reader: &mut R) -> io::Result<(usize, usize)> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, format!("PPM: {}", msg));
+
+    // magic, width, height and maxval, the whitespace after maxval is the last header byte
+    let mut fields = Vec::with_capacity(4);
+    let mut field = Vec::new();
+    let mut byte = [0];
+    while fields.len() < 4 {
+        reader.read_exact(&mut byte)?;
+        match byte[0] {
+            b'#' if field.is_empty() => {
+                reader.read_until(b'\n', &mut Vec::new())?;
+            }
+            c if c.is_ascii_whitespace() => {
+                if !field.is_empty() {
+                    fields.push(String::from_utf8_lossy(&field).into_owned());
+                    field.clear();
+                }
+            }
+            c => field.push(c),
+        }
+    }
+
+    if fields[0] != "P6" {
+        return Err(invalid("only binary (P6) images are supported"));
+    }
+    let number = |s: &str| s.parse::<usize>().map_err(|_| invalid("bad header"));
+    let (width, height) = (number(&fields[1])?, number(&fields[2])?);
+    if number(&fields[3])? != 255 {
+        return Err(invalid("only 8-bit images are supported"));
+    }
+    Ok((width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stdin_image() {
+        let img = read_stdin_image(
+            &b"P6\n# made by hand\n2 1\n255\n\x01\x02\x03\x04\x05\x06"[..],
+            StdinFormat::Ppm,
+            0,
+            0,
+            2,
+        )
+        .unwrap();
+        assert_eq!((img.width(), img.height()), (2, 1));
+        assert_eq!(
+            img.buf(),
+            &[
+                BGRA8 {
+                    b: 3,
+                    g: 2,
+                    r: 1,
+                    a: 255
+                },
+                BGRA8 {
+                    b: 6,
+                    g: 5,
+                    r: 4,
+                    a: 255
+                }
+            ]
+        );
+
+        let img = read_stdin_image(&[1, 2, 3, 4][..], StdinFormat::Rgba, 1, 1, 1).unwrap();
+        assert_eq!(
+            img.buf(),
+            &[BGRA8 {
+                b: 3,
+                g: 2,
+                r: 1,
+                a: 4
+            }]
+        );
+
+        assert!(read_stdin_image(&b"P3\n1 1\n255\n0 0 0"[..], StdinFormat::Ppm, 0, 0, 1).is_err());
+        assert!(read_stdin_image(&[1, 2][..], StdinFormat::Rgb, 1, 1, 1).is_err());
+        assert!(read_stdin_image(&b"P6\n2 1\n255\n"[..], StdinFormat::Ppm, 0, 0, 1).is_err());
+        assert!(read_stdin_image(&[][..], StdinFormat::Bgra, usize::MAX, 2, usize::MAX).is_err());
+    }
+}
@@ -1,25 +1,182 @@
 use std::ffi::OsString;
-use std::num::{NonZeroU8, NonZeroUsize};
+use std::num::{NonZeroU64, NonZeroU8, NonZeroUsize};
 use std::path::PathBuf;
 
+use structopt::clap::AppSettings;
 use structopt::StructOpt;
 
+mod types;
 mod validators;
 
+pub use types::*;
+
 /// Distort a screenshot and run i3lock
 // Needs to be fixed upstream in StructOpt
 // TODO: checked if my PR is merged
 #[derive(StructOpt, Debug)]
+#[structopt(global_settings = &[AppSettings::AllArgsOverrideSelf])]
 pub struct Cli {
     /// Prints version information
     #[structopt(short = "V", long = "version", alias = "vers")]
     pub version: bool,
 
-    /// Print how long each step takes, among other things.
+    /// Config file to read options from. Options given on the command line take precedence.
+    /// Defaults to $XDG_CONFIG_HOME/i3lockr/config.toml
+    #[structopt(long = "config", value_name = "file.toml", parse(from_os_str))]
+    pub config: Option<PathBuf>,
+
+    /// Apply the options in the config file's [profile.<name>] table on top of the rest of it.
+    #[structopt(long = "profile", value_name = "name")]
+    pub profile: Option<String>,
+
+    /// Print the options in effect, including the config file's and the defaults,
+    /// as a config file and exit.
+    #[structopt(long = "export-config")]
+    pub export_config: bool,
+
+    /// Print how long each step takes, among other things. Same as --log-level debug.
     /// Always enabled in debug builds.
     #[structopt(short = "v", long = "verbose", alias = "verb", alias = "debug")]
     pub verbose: bool,
 
+    /// Don't color warnings and errors. Also turned off by setting $NO_COLOR.
+    #[structopt(long = "no-color")]
+    pub no_color: bool,
+
+    /// Don't print anything but errors, not even warnings. Same as --log-level error.
+    #[structopt(short = "q", long = "quiet", conflicts_with_all = &["verbose", "verbose-stages"])]
+    pub quiet: bool,
+
+    /// Only print messages at least this important. Defaults to $RUST_LOG, or warn.
+    #[structopt(
+        long = "log-level",
+        value_name = "level",
+        possible_values = LogLevel::VARIANTS,
+        conflicts_with_all = &["verbose", "quiet"]
+    )]
+    pub log_level: Option<LogLevel>,
+
+    /// Like --verbose, but only time the stages that take at least this many milliseconds.
+    /// Example: 50
+    #[structopt(long = "verbose-stages", value_name = "ms")]
+    pub verbose_stages: Option<u64>,
+
+    /// Exit with code 2 and a short message if there is no display to lock,
+    /// instead of failing somewhere along the way. Useful for headless machines.
+    #[structopt(long = "fail-on-no-display")]
+    pub fail_on_no_display: bool,
+
+    /// Use an image read from stdin instead of a screenshot, raw BGRA unless
+    /// --stdin-image-format says otherwise. Raw images are --stdin-width by
+    /// --stdin-height, which default to the size of all displays combined.
+    #[structopt(long = "read-image-stdin")]
+    pub read_image_stdin: bool,
+
+    /// Format of the --read-image-stdin image. PPM images carry their own size,
+    /// the others are --stdin-width by --stdin-height.
+    #[structopt(
+        long = "stdin-image-format",
+        value_name = "format",
+        default_value = "bgra",
+        possible_values = StdinFormat::VARIANTS
+    )]
+    pub stdin_image_format: StdinFormat,
+
+    /// Width of the raw --read-image-stdin image. Defaults to the width of all displays.
+    #[structopt(long = "stdin-width", requires = "read-image-stdin")]
+    pub stdin_width: Option<NonZeroUsize>,
+
+    /// Height of the raw --read-image-stdin image. Defaults to the height of all displays.
+    #[structopt(long = "stdin-height", requires = "read-image-stdin")]
+    pub stdin_height: Option<NonZeroUsize>,
+
+    /// Use a file of raw BGRA bytes instead of a screenshot, without touching the display.
+    /// The whole image is treated as a single monitor.
+    #[structopt(
+        long = "raw-input",
+        value_name = "file",
+        parse(from_os_str),
+        requires_all = &["raw-width", "raw-height"],
+        conflicts_with = "read-image-stdin"
+    )]
+    pub raw_input: Option<PathBuf>,
+
+    /// Width of the --raw-input image.
+    #[structopt(long = "raw-width", requires = "raw-input")]
+    pub raw_width: Option<NonZeroUsize>,
+
+    /// Height of the --raw-input image.
+    #[structopt(long = "raw-height", requires = "raw-input")]
+    pub raw_height: Option<NonZeroUsize>,
+
+    /// Lock with a checkerboard instead of a screenshot, for testing effects.
+    #[structopt(
+        long = "checkerboard",
+        value_name = "size:color:color",
+        conflicts_with_all = &["raw-input", "read-image-stdin"]
+    )]
+    pub checkerboard: Option<Checkerboard>,
+
+    /// Lock with this image, stretched to the screen, instead of a screenshot.
+    #[structopt(
+        long = "background-image",
+        value_name = "file.png",
+        parse(from_os_str),
+        validator = validators::has_compose,
+        conflicts_with_all = &["raw-input", "read-image-stdin", "checkerboard"]
+    )]
+    pub background_image: Option<PathBuf>,
+
+    /// How --background-image and --icon-scale resize images.
+    #[structopt(
+        long = "scale-quality",
+        default_value = "nearest",
+        possible_values = ScaleQuality::VARIANTS
+    )]
+    pub scale_quality: ScaleQuality,
+
+    /// Give up before taking the screenshot if it and the effects are estimated
+    /// to need more than this many MiB. Example: 512
+    #[structopt(long = "max-memory", value_name = "mb")]
+    pub max_memory: Option<NonZeroUsize>,
+
+    /// How to take the screenshot: scrap, XGetImage on the root window (ximagesrc),
+    /// or a single frame from `ffmpeg -f x11grab`.
+    #[structopt(
+        long = "screenshot-method",
+        default_value = "scrap",
+        possible_values = ScreenshotMethod::VARIANTS
+    )]
+    pub screenshot_method: ScreenshotMethod,
+
+    /// Throw away this many frames before taking the screenshot,
+    /// for drivers that return black frames at first. Only used by scrap. Example: 3
+    #[structopt(long = "screenshot-delay-frames", default_value = "0")]
+    pub screenshot_delay_frames: usize,
+
+    /// Run a fixed set of effects on a built-in image and check the result
+    /// against a known hash, then exit. Needs the "self-test" feature.
+    #[structopt(long = "self-test")]
+    pub self_test: bool,
+
+    /// Check that the arguments make sense together, list any problems, then exit.
+    /// Nothing is captured or locked.
+    #[structopt(long = "format-check")]
+    pub format_check: bool,
+
+    /// Run the selected effects on a blank image and print how long they take, then exit
+    /// without locking. The icon is not overlaid.
+    #[structopt(long = "benchmark-effects")]
+    pub benchmark_effects: bool,
+
+    /// Width of the --benchmark-effects image. Defaults to the primary display's width.
+    #[structopt(long = "width", requires = "benchmark-effects")]
+    pub width: Option<NonZeroUsize>,
+
+    /// Height of the --benchmark-effects image. Defaults to the primary display's height.
+    #[structopt(long = "height", requires = "benchmark-effects")]
+    pub height: Option<NonZeroUsize>,
+
     /// Darken the screenshot by [1, 255]. Example: 15
     #[structopt(long = "darken", visible_alias = "dark", conflicts_with = "bright")]
     pub dark: Option<NonZeroU8>,
@@ -28,14 +185,235 @@ pub struct Cli {
     #[structopt(long = "brighten", visible_alias = "bright")]
     pub bright: Option<NonZeroU8>,
 
+    /// Only brighten or darken this channel.
+    #[structopt(
+        long = "channel",
+        default_value = "all",
+        possible_values = Channel::VARIANTS
+    )]
+    pub channel: Channel,
+
     /// Blur strength. Example: 10
     #[structopt(short = "b", long = "blur", alias = "rad")]
     pub radius: Option<NonZeroUsize>,
 
+    /// Gaussian blur with this standard deviation instead of --blur's radius,
+    /// reaching 3 sigma out. Example: 2.5
+    #[structopt(
+        long = "gaussian-sigma",
+        value_name = "sigma",
+        conflicts_with_all = &["radius", "adaptive-blur"],
+        validator = validators::is_positive
+    )]
+    pub gaussian_sigma: Option<f32>,
+
+    /// Blur the alpha channel on its own, with --blur-alpha-radius or --blur's radius.
+    /// Only matters for screenshots with meaningful alpha, such as composited output.
+    #[structopt(long = "blur-alpha")]
+    pub blur_alpha: bool,
+
+    /// Radius to blur the alpha channel with for --blur-alpha. Example: 4
+    #[structopt(
+        long = "blur-alpha-radius",
+        value_name = "radius",
+        requires = "blur-alpha"
+    )]
+    pub blur_alpha_radius: Option<NonZeroUsize>,
+
+    /// Blur everything but a clear square in the middle of each monitor,
+    /// before any other effect. Example: 20
+    #[structopt(long = "background-blur", value_name = "radius")]
+    pub background_blur: Option<NonZeroUsize>,
+
+    /// Size of the square --background-blur leaves clear, in pixels.
+    #[structopt(
+        long = "background-clear-size",
+        value_name = "pixels",
+        default_value = "400",
+        requires = "background-blur"
+    )]
+    pub background_clear_size: usize,
+
+    /// Allow blur radii up to this instead of 50. Large radii can take a very long time,
+    /// --scale is usually the better way to blur harder. Example: 100
+    #[structopt(long = "max-blur-radius-override", value_name = "radius")]
+    pub max_blur_radius_override: Option<NonZeroUsize>,
+
+    /// Blur each monitor separately, scaling this radius by the monitor's size
+    /// relative to 1080p. Example: 10
+    #[structopt(long = "adaptive-blur", conflicts_with = "radius")]
+    pub adaptive_blur: Option<NonZeroU8>,
+
+    /// Double the blur radius on monitors denser than --dpi-threshold.
+    #[structopt(long = "detect-retina")]
+    pub detect_retina: bool,
+
+    /// DPI above which --detect-retina doubles the blur radius.
+    #[structopt(
+        long = "dpi-threshold",
+        value_name = "dpi",
+        default_value = "150",
+        validator = validators::is_positive
+    )]
+    pub dpi_threshold: f32,
+
     /// Scale factor. Increases blur strength by a factor of this. Example: 2
     #[structopt(short = "p", long = "scale")]
     pub factor: Option<NonZeroUsize>,
 
+    /// Only scale down, the result stays 1/--scale the size of the screen.
+    /// Mostly useful with --output-png.
+    #[structopt(
+        long = "scale-down-only",
+        requires = "factor",
+        conflicts_with = "scale-up-only"
+    )]
+    pub scale_down_only: bool,
+
+    /// Don't scale down, blur at full resolution and then scale the top left
+    /// 1/--scale of the image up to fill the screen.
+    #[structopt(long = "scale-up-only", requires = "factor")]
+    pub scale_up_only: bool,
+
+    /// Multiply each pixel's RGB by this row-major 3x3 matrix.
+    /// Example: 0.393,0.769,0.189,0.349,0.686,0.168,0.272,0.534,0.131 for sepia
+    #[structopt(
+        long = "color-matrix",
+        value_name = "3x3_matrix",
+        allow_hyphen_values = true
+    )]
+    pub color_matrix: Option<ColorMatrix>,
+
+    /// Stretch the contrast so that the darkest and brightest 1% of the image
+    /// become black and white (`color-grading` feature).
+    #[structopt(long = "auto-contrast")]
+    pub auto_contrast: bool,
+
+    /// Tint the shadows, midtones and highlights, 808080 leaves a range alone.
+    /// Example: 8080a0:808080:a09080 for cool shadows and warm highlights
+    #[structopt(long = "color-balance", value_name = "shadows:midtones:highlights")]
+    pub color_balance: Option<ColorBalance>,
+
+    /// Reduce the image to this many colors, for a flat, posterized look.
+    #[structopt(long = "color-quantize", value_name = "colors")]
+    pub color_quantize: Option<NonZeroUsize>,
+
+    /// Convolve the image with this square kernel, given row by row.
+    /// Example: 0,-1,0,-1,5,-1,0,-1,0 to sharpen
+    #[structopt(long = "kernel", value_name = "values", allow_hyphen_values = true)]
+    pub kernel: Option<Kernel>,
+
+    /// Scale --kernel so that its values add up to 1.
+    #[structopt(long = "kernel-normalize", requires = "kernel")]
+    pub kernel_normalize: bool,
+
+    /// Turn everything gray except colors with a hue in this range of degrees,
+    /// which may wrap around. Example: 330:30 keeps reds
+    #[structopt(long = "desaturate-except", value_name = "hue_range")]
+    pub desaturate_except: Option<HueRange>,
+
+    /// Replace the image with these colors, each scaled by how bright its channel is,
+    /// `all` uses the luma. Must be comma separated (`color-grading` feature).
+    /// Example: r:ff0000 keeps only the red component
+    #[structopt(long = "dye", value_name = "channel:color", require_delimiter = true)]
+    pub dye: Vec<Dye>,
+
+    /// Pixelate the screenshot into blocks this big. Example: 8
+    #[structopt(long = "pixelate", value_name = "size")]
+    pub pixelate: Option<NonZeroUsize>,
+
+    /// Skip all effects and the icon, and lock with the plain screenshot.
+    /// Handy for telling i3lockr's own overhead apart from the effects'.
+    #[structopt(long = "no-effects")]
+    pub no_effects: bool,
+
+    /// Order to apply effects in, each still needs its own option to do anything.
+    /// Defaults to scale-down,blur,scale-up,pixelate,auto-contrast,brighten,darken,color-matrix,color-balance,desaturate,dye,kernel,quantize,mirror,shadow,overlay
+    #[structopt(
+        long = "pipeline",
+        value_name = "blur,darken,overlay",
+        require_delimiter = true,
+        possible_values = PipelineStep::VARIANTS
+    )]
+    pub pipeline: Vec<PipelineStep>,
+
+    /// Leave the top this many rows of each monitor untouched by the effects and the icon,
+    /// e.g. to keep a status bar readable. Example: 24
+    #[structopt(long = "title-bar-crop", value_name = "height")]
+    pub title_bar_crop: Option<NonZeroUsize>,
+
+    /// Fade the bottom of each monitor to black, like a drop shadow.
+    #[structopt(long = "shadow-overlay")]
+    pub shadow_overlay: bool,
+
+    /// Ask the xrandr binary for the monitor layout if RandR can't be queried directly,
+    /// e.g. on X servers without the extension.
+    #[structopt(long = "xrandr-fallback")]
+    pub xrandr_fallback: bool,
+
+    /// Ask RandR again up to this many times, 100 ms apart, while it reports no active monitors.
+    /// For systems where RandR is slow to catch up after hotplugging. Example: 3
+    #[structopt(long = "detect-displays-retry", value_name = "n", default_value = "0")]
+    pub detect_displays_retry: usize,
+
+    /// Get the monitor layout from Xinerama instead of RandR, for legacy multi-head setups.
+    #[structopt(long = "xinerama-mode", conflicts_with = "xrandr-fallback")]
+    pub xinerama_mode: bool,
+
+    /// Draw a grid of cols x rows cells over the whole image. Example: 16,9,ffffff,0.3
+    #[structopt(long = "grid-overlay", value_name = "cols,rows,color,opacity")]
+    pub grid_overlay: Option<GridOverlay>,
+
+    /// Write this text diagonally across every monitor, over and over (`text` feature).
+    /// Example: CONFIDENTIAL
+    #[structopt(long = "watermark", value_name = "text")]
+    pub watermark: Option<String>,
+
+    /// Color of the --watermark text.
+    #[structopt(
+        long = "watermark-color",
+        value_name = "rrggbb",
+        default_value = "ffffff",
+        parse(try_from_str = parse_rgb)
+    )]
+    pub watermark_color: (u8, u8, u8),
+
+    /// Opacity of the --watermark text, from 0 to 1.
+    #[structopt(
+        long = "watermark-opacity",
+        value_name = "opacity",
+        default_value = "0.15",
+        validator = validators::is_fraction
+    )]
+    pub watermark_opacity: f32,
+
+    /// Height of the --watermark text in pixels.
+    #[structopt(
+        long = "watermark-size",
+        value_name = "pixels",
+        default_value = "48",
+        validator = validators::is_positive
+    )]
+    pub watermark_size: f32,
+
+    /// Font file for --watermark. Defaults to fontconfig's sans-serif font.
+    #[structopt(long = "watermark-font", value_name = "file", parse(from_os_str))]
+    pub watermark_font: Option<PathBuf>,
+
+    /// Print the detected monitors as JSON and exit.
+    #[structopt(long = "monitor-info-json")]
+    pub monitor_info_json: bool,
+
+    /// Save the image as a PNG in this directory after every pipeline step,
+    /// as 01_screenshot.png, 02_scale_down.png and so on (`debug-stages` feature).
+    #[structopt(long = "debug-save-stages", value_name = "dir", parse(from_os_str))]
+    pub debug_save_stages: Option<PathBuf>,
+
+    /// Draw a colored border around each detected monitor and log its number and color.
+    /// Useful for working out --ignore-monitors.
+    #[structopt(long = "debug-monitors")]
+    pub debug_monitors: bool,
+
     /// Don't overlay an icon on these monitors. Useful if you're mirroring displays. Must be comma separated.
     /// Example: 0,2
     #[structopt(
@@ -47,6 +425,38 @@ pub struct Cli {
     )]
     pub ignore: Vec<usize>,
 
+    /// Ignore the monitors connected to these RandR outputs, which unlike indices don't
+    /// change when monitors are plugged in. Must be comma separated. Example: HDMI-1,DP-2
+    #[structopt(
+        long = "ignore-monitors-named",
+        value_name = "names",
+        require_delimiter = true
+    )]
+    pub ignore_named: Vec<String>,
+
+    /// Mirror these monitors left to right, before the icon is drawn. Must be comma separated.
+    /// Example: 1
+    #[structopt(long = "mirror-x", value_name = "0,2", require_delimiter = true)]
+    pub mirror_x: Vec<usize>,
+
+    /// Mirror these monitors top to bottom, before the icon is drawn. Must be comma separated.
+    #[structopt(long = "mirror-y", value_name = "0,2", require_delimiter = true)]
+    pub mirror_y: Vec<usize>,
+
+    /// How monitors are numbered for --ignore-monitors. "logical" is the order RandR
+    /// reports them in, "physical" sorts them left to right.
+    #[structopt(
+        long = "monitor-order",
+        value_name = "order",
+        default_value = "logical",
+        possible_values = MonitorOrder::VARIANTS
+    )]
+    pub monitor_order: MonitorOrder,
+
+    /// Don't let the icon spill over onto neighbouring monitors.
+    #[structopt(long = "clamp-icon-to-monitor", validator = validators::has_compose)]
+    pub clamp_icon_to_monitor: bool,
+
     /// Interpret the icon as a mask, inverting masked pixels
     /// on the screenshot. Try it to see an example.
     #[structopt(long = "invert", validator = validators::has_compose)]
@@ -54,7 +464,8 @@ pub struct Cli {
 
     /// Icon placement, "x,y" (from top-left), or "-x,-y" (from bottom-right).
     /// Has no effect without --icon. Must be comma separated. Defaults to center if not specified.
-    /// Example: "945,-20"
+    /// Fractional positions are rendered with bilinear filtering.
+    /// Example: "945,-20" or "945.5,-20.25"
     #[structopt(
         short = "u",
         long = "position",
@@ -65,9 +476,29 @@ pub struct Cli {
         validator = validators::has_compose,
         visible_alias = "pos"
     )]
-    pub pos: Vec<isize>,
+    pub pos: Vec<f32>,
 
-    /// Path to icon to overlay on screenshot.
+    /// Draw the icon this many times on each monitor, evenly spaced along the diagonal
+    /// from the top left corner towards the bottom right. Example: 4
+    #[structopt(
+        long = "icon-repeat-diagonal",
+        value_name = "n",
+        conflicts_with_all = &["pos", "center-on-cursor"]
+    )]
+    pub icon_repeat_diagonal: Option<NonZeroUsize>,
+
+    /// Use an icon that is larger than a monitor instead of refusing to lock.
+    #[structopt(long = "force-large-icon")]
+    pub force_large_icon: bool,
+
+    /// Center the icon on the mouse pointer, on the monitor it is on only.
+    /// Overrides --position.
+    #[structopt(long = "center-on-cursor")]
+    pub center_on_cursor: bool,
+
+    /// Path to icon to overlay on screenshot. May also be an http(s) URL
+    /// if compiled with the "remote-icon" feature.
+    /// Defaults to $I3LOCKR_ICON, or $IMAGE_PATH.
     #[structopt(
         short = "i",
         long = "icon",
@@ -77,6 +508,231 @@ pub struct Cli {
     )]
     pub path: Option<PathBuf>,
 
+    /// Use the image in the X clipboard as the icon (`clipboard` feature).
+    #[structopt(
+        long = "icon-from-clipboard",
+        conflicts_with = "path",
+        validator = validators::has_compose
+    )]
+    pub icon_from_clipboard: bool,
+
+    /// Add this many pixels of transparent padding around the icon,
+    /// before it is scaled or rotated. Example: 20
+    #[structopt(long = "pad-icon", value_name = "pixels", validator = validators::has_compose)]
+    pub pad_icon: Option<usize>,
+
+    /// Resize the icon by this factor before overlaying it. Example: 0.5
+    #[structopt(long = "icon-scale", value_name = "factor", validator = validators::is_positive)]
+    pub icon_scale: Option<f32>,
+
+    /// Overlay the icon before the effects, so it gets blurred along with the screenshot.
+    #[structopt(long = "icon-blend-behind", conflicts_with = "pipeline")]
+    pub icon_blend_behind: bool,
+
+    /// Make icon pixels with at least this alpha fully opaque and the rest fully transparent,
+    /// for logos with sharp edges. Example: 128
+    #[structopt(long = "icon-mask", value_name = "alpha_threshold")]
+    pub icon_mask: Option<u8>,
+
+    /// Multiply the alpha of every icon pixel by this factor, after --icon-mask. Example: 0.5
+    #[structopt(
+        long = "icon-alpha-multiply",
+        value_name = "factor",
+        validator = validators::is_positive
+    )]
+    pub icon_alpha_multiply: Option<f32>,
+
+    /// Tint the icon by multiplying its colors with this one, a white icon becomes this color.
+    /// Example: ff0000
+    #[structopt(
+        long = "icon-colorize",
+        value_name = "rrggbb",
+        parse(try_from_str = parse_rgb)
+    )]
+    pub icon_colorize: Option<(u8, u8, u8)>,
+
+    /// Draw an outline this many pixels wide around the opaque part of the icon. Example: 2:ffffff
+    #[structopt(
+        long = "icon-outline",
+        value_name = "width:color",
+        validator = validators::has_compose
+    )]
+    pub icon_outline: Option<IconOutline>,
+
+    /// Mirror the icon before overlaying it.
+    #[structopt(
+        long = "icon-flip",
+        value_name = "mode",
+        possible_values = FlipMode::VARIANTS
+    )]
+    pub icon_flip: Option<FlipMode>,
+
+    /// Rotate the icon clockwise by this many degrees before overlaying it. Example: 45
+    #[structopt(
+        long = "icon-rotate",
+        value_name = "degrees",
+        allow_hyphen_values = true,
+        validator = validators::is_finite
+    )]
+    pub icon_rotate: Option<f32>,
+
+    /// Scale the icon down to fit in this size, after any other icon option,
+    /// keeping its aspect ratio. Example: 512x512
+    #[structopt(long = "max-icon-size", value_name = "WxH")]
+    pub max_icon_size: Option<Size>,
+
+    /// The icon uses premultiplied alpha. Without this, semi-transparent parts of
+    /// such icons look washed out.
+    #[structopt(long = "premultiplied", validator = validators::has_compose)]
+    pub premultiplied: bool,
+
+    /// How long to reuse a downloaded icon before fetching it again, in seconds.
+    #[structopt(
+        long = "icon-cache-ttl",
+        value_name = "seconds",
+        default_value = "86400"
+    )]
+    pub icon_cache_ttl: u64,
+
+    /// Wait this many seconds before taking the screenshot and locking, counting down
+    /// in a desktop notification (`notify` feature). Example: 30
+    #[structopt(long = "lock-after", value_name = "seconds")]
+    pub lock_after: Option<NonZeroU64>,
+
+    /// Send a desktop notification, then wait this long before taking the screenshot
+    /// and locking (`notify` feature). Example: 5000
+    #[structopt(long = "notify-before-lock", value_name = "ms")]
+    pub notify_before_lock: Option<NonZeroU64>,
+
+    /// Show the lock screen for this long before asking for a password, rounded up to whole
+    /// seconds. Requires an i3lock that supports --grace, such as i3lock-color.
+    #[structopt(long = "delay-lock", value_name = "ms")]
+    pub delay_lock: Option<NonZeroU64>,
+
+    /// Have i3lock give up on PAM authentication after this many seconds, passed on as
+    /// --auth-timeout. Requires an i3lock that supports it.
+    #[structopt(long = "pam-auth-timeout", value_name = "seconds")]
+    pub pam_auth_timeout: Option<NonZeroU64>,
+
+    /// Pass --debug to i3lock if it supports it, its log ends up on i3lockr's stderr.
+    /// Helps with authentication problems.
+    #[structopt(long = "verbose-i3lock")]
+    pub verbose_i3lock: bool,
+
+    /// Shell command to run after i3lock exits. Requires --nofork to be passed to i3lock.
+    /// Example: "playerctl play"
+    #[structopt(long = "after-lock", value_name = "command")]
+    pub after_lock: Option<String>,
+
+    /// Show the processed image in feh first, and lock once its window is closed.
+    /// Handy for tuning the effects.
+    #[structopt(long = "preview")]
+    pub preview: bool,
+
+    /// Set the processed image as the root window's background instead of calling i3lock.
+    #[structopt(long = "apply-to-root", conflicts_with = "stdout")]
+    pub apply_to_root: bool,
+
+    /// Save the processed image as a PNG instead of calling i3lock.
+    #[structopt(
+        long = "output-png",
+        value_name = "file.png",
+        parse(from_os_str),
+        conflicts_with_all = &["stdout", "apply-to-root"]
+    )]
+    pub output_png: Option<PathBuf>,
+
+    /// Expand scrot-style %Y, %m, %d, %H, %M and %S in --output-png and --output-bmp
+    /// to the current local time, if compiled with the "scrot" feature.
+    /// Example: ~/Pictures/lock_%Y-%m-%d_%H%M%S.png
+    #[structopt(long = "scrot-compat")]
+    pub scrot_compat: bool,
+
+    /// Save the processed image as a BMP instead of calling i3lock, works without the png feature.
+    #[structopt(
+        long = "output-bmp",
+        value_name = "file.bmp",
+        parse(from_os_str),
+        conflicts_with_all = &["stdout", "apply-to-root", "output-png"]
+    )]
+    pub output_bmp: Option<PathBuf>,
+
+    /// Write the processed image to stdout instead of calling i3lock.
+    #[structopt(long = "stdout")]
+    pub stdout: bool,
+
+    /// Image format used by --stdout.
+    #[structopt(
+        long = "stdout-format",
+        value_name = "format",
+        default_value = "raw",
+        possible_values = OutputFormat::VARIANTS
+    )]
+    pub stdout_format: OutputFormat,
+
+    /// Start raw --stdout output with a header giving the image's size and pixel format.
+    #[structopt(long = "format-raw-header", requires = "stdout")]
+    pub format_raw_header: bool,
+
+    /// Only hand i3lock the part of the image on this monitor, leaving the others visible.
+    /// Needs a compositor that supports it. Example: DP-1
+    #[structopt(long = "lockscreen-only-on", value_name = "monitor_name")]
+    pub lockscreen_only_on: Option<String>,
+
+    /// Pixel layout of the image handed to i3lock.
+    #[structopt(
+        long = "pixel-format",
+        value_name = "format",
+        default_value = "bgra",
+        possible_values = PixelFormat::VARIANTS
+    )]
+    pub pixel_format: PixelFormat,
+
+    /// Drop the alpha channel and hand i3lock plain RGB, short for --pixel-format rgb.
+    #[structopt(long = "strip-alpha", conflicts_with = "pixel-format")]
+    pub strip_alpha: bool,
+
+    /// Exit straight away if another i3lockr started with this option is still running,
+    /// using a lock file in $XDG_RUNTIME_DIR.
+    #[structopt(long = "xdg-runtime-lock")]
+    pub xdg_runtime_lock: bool,
+
+    /// Refuse to lock if i3lock is older than this version. Example: 2.13
+    #[structopt(
+        long = "version-pin",
+        value_name = "major.minor",
+        parse(try_from_str = parse_version)
+    )]
+    pub version_pin: Option<(u32, u32)>,
+
+    /// Don't check that i3lock supports --raw before doing anything else.
+    #[structopt(long = "skip-i3lock-check")]
+    pub skip_i3lock_check: bool,
+
+    /// Gzip the image handed to i3lock. Stock i3lock can't read it, so this only
+    /// takes effect together with --i3lock-supports-gzip.
+    #[structopt(long = "compress-output")]
+    pub compress_output: bool,
+
+    /// The installed i3lock reads gzip-compressed images, see --compress-output.
+    #[structopt(long = "i3lock-supports-gzip")]
+    pub i3lock_supports_gzip: bool,
+
+    /// Take a new screenshot and lock again, up to this many times, whenever i3lock exits
+    /// with an error. Only works with --nofork, and only with lockers that exit on a wrong
+    /// password, stock i3lock just asks again.
+    #[structopt(
+        long = "retry-on-wrong-password",
+        value_name = "n",
+        default_value = "0"
+    )]
+    pub retry_on_wrong_password: usize,
+
+    /// Also pass the arguments in this file to i3lock, one per line.
+    /// Blank lines and lines starting with # are ignored.
+    #[structopt(long = "i3lock-args-file", value_name = "file", parse(from_os_str))]
+    pub i3lock_args_file: Option<PathBuf>,
+
     /// Arguments to pass to i3lock. Example: "--nofork --ignore-empty-password"
     #[structopt(
         value_name = "i3lock",
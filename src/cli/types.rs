@@ -0,0 +1,760 @@
+use std::fmt;
+use std::num::NonZeroUsize;
+use std::str::FromStr;
+
+/// Image format used by `--stdout`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Raw,
+    Ppm,
+    Bmp,
+}
+
+impl OutputFormat {
+    pub const VARIANTS: &'static [&'static str] = &["raw", "ppm", "bmp"];
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "raw" => Ok(Self::Raw),
+            "ppm" => Ok(Self::Ppm),
+            "bmp" => Ok(Self::Bmp),
+            _ => Err(format!("Unknown output format: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Raw => "raw",
+            Self::Ppm => "ppm",
+            Self::Bmp => "bmp",
+        })
+    }
+}
+
+/// Layout of the image read by `--read-image-stdin`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StdinFormat {
+    Bgra,
+    Rgba,
+    Rgb,
+    Ppm,
+}
+
+impl StdinFormat {
+    pub const VARIANTS: &'static [&'static str] = &["bgra", "rgba", "rgb", "ppm"];
+}
+
+impl FromStr for StdinFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bgra" => Ok(Self::Bgra),
+            "rgba" => Ok(Self::Rgba),
+            "rgb" => Ok(Self::Rgb),
+            "ppm" => Ok(Self::Ppm),
+            _ => Err(format!("Unknown stdin image format: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for StdinFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Bgra => "bgra",
+            Self::Rgba => "rgba",
+            Self::Rgb => "rgb",
+            Self::Ppm => "ppm",
+        })
+    }
+}
+
+/// Least important messages printed for `--log-level`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    pub const VARIANTS: &'static [&'static str] = &["error", "warn", "info", "debug", "trace"];
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(Self::Error),
+            "warn" => Ok(Self::Warn),
+            "info" => Ok(Self::Info),
+            "debug" => Ok(Self::Debug),
+            "trace" => Ok(Self::Trace),
+            _ => Err(format!("Unknown log level: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Error => "error",
+            Self::Warn => "warn",
+            Self::Info => "info",
+            Self::Debug => "debug",
+            Self::Trace => "trace",
+        })
+    }
+}
+
+/// Pixel layout handed to i3lock, for `--pixel-format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    Bgra8,
+    Rgba8,
+    Rgb8,
+    Bgr8,
+}
+
+impl PixelFormat {
+    pub const VARIANTS: &'static [&'static str] = &["bgra", "rgba", "rgb", "bgr"];
+
+    pub const fn bytes_per_pixel(self) -> usize {
+        match self {
+            Self::Bgra8 | Self::Rgba8 => 4,
+            Self::Rgb8 | Self::Bgr8 => 3,
+        }
+    }
+}
+
+impl FromStr for PixelFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bgra" => Ok(Self::Bgra8),
+            "rgba" => Ok(Self::Rgba8),
+            "rgb" => Ok(Self::Rgb8),
+            "bgr" => Ok(Self::Bgr8),
+            _ => Err(format!("Unknown pixel format: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for PixelFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Bgra8 => "bgra",
+            Self::Rgba8 => "rgba",
+            Self::Rgb8 => "rgb",
+            Self::Bgr8 => "bgr",
+        })
+    }
+}
+
+/// How monitors are numbered for `--ignore-monitors`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MonitorOrder {
+    /// The order RandR reports CRTCs in.
+    Logical,
+    /// Left to right, then top to bottom.
+    Physical,
+}
+
+impl MonitorOrder {
+    pub const VARIANTS: &'static [&'static str] = &["logical", "physical"];
+}
+
+impl FromStr for MonitorOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "logical" => Ok(Self::Logical),
+            "physical" => Ok(Self::Physical),
+            _ => Err(format!("Unknown monitor order: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for MonitorOrder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Logical => "logical",
+            Self::Physical => "physical",
+        })
+    }
+}
+
+/// Color channel adjusted by `--brighten` and `--darken`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    R,
+    G,
+    B,
+    All,
+}
+
+impl Channel {
+    pub const VARIANTS: &'static [&'static str] = &["r", "g", "b", "all"];
+}
+
+impl FromStr for Channel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "r" => Ok(Self::R),
+            "g" => Ok(Self::G),
+            "b" => Ok(Self::B),
+            "all" => Ok(Self::All),
+            _ => Err(format!("Unknown channel: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::R => "r",
+            Self::G => "g",
+            Self::B => "b",
+            Self::All => "all",
+        })
+    }
+}
+
+/// Screenshot backend for `--screenshot-method`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreenshotMethod {
+    Scrap,
+    Ximagesrc,
+    Ffmpeg,
+}
+
+impl ScreenshotMethod {
+    pub const VARIANTS: &'static [&'static str] = &["scrap", "ximagesrc", "ffmpeg"];
+}
+
+impl FromStr for ScreenshotMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "scrap" => Ok(Self::Scrap),
+            "ximagesrc" => Ok(Self::Ximagesrc),
+            "ffmpeg" => Ok(Self::Ffmpeg),
+            _ => Err(format!("Unknown screenshot method: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for ScreenshotMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Scrap => "scrap",
+            Self::Ximagesrc => "ximagesrc",
+            Self::Ffmpeg => "ffmpeg",
+        })
+    }
+}
+
+/// Filter used when resizing images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScaleQuality {
+    Nearest,
+    Bilinear,
+}
+
+impl ScaleQuality {
+    pub const VARIANTS: &'static [&'static str] = &["nearest", "bilinear"];
+}
+
+impl FromStr for ScaleQuality {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nearest" => Ok(Self::Nearest),
+            "bilinear" => Ok(Self::Bilinear),
+            _ => Err(format!("Unknown scale quality: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for ScaleQuality {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Nearest => "nearest",
+            Self::Bilinear => "bilinear",
+        })
+    }
+}
+
+/// Axis to mirror along for `--icon-flip`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlipMode {
+    Horizontal,
+    Vertical,
+    Both,
+}
+
+impl FlipMode {
+    pub const VARIANTS: &'static [&'static str] = &["horizontal", "vertical", "both"];
+}
+
+impl FromStr for FlipMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "horizontal" => Ok(Self::Horizontal),
+            "vertical" => Ok(Self::Vertical),
+            "both" => Ok(Self::Both),
+            _ => Err(format!("Unknown flip mode: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for FlipMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Horizontal => "horizontal",
+            Self::Vertical => "vertical",
+            Self::Both => "both",
+        })
+    }
+}
+
+/// Row-major 3x3 matrix for `--color-matrix`, applied to `[r, g, b]`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorMatrix(pub [[f32; 3]; 3]);
+
+impl FromStr for ColorMatrix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|v| v.trim().parse::<f32>().ok().filter(|v| v.is_finite()))
+            .collect::<Option<Vec<_>>>()
+            .filter(|v| v.len() == 9)
+            .ok_or_else(|| format!("Expected 9 comma separated numbers, got '{}'", s))?;
+        let mut matrix = [[0.0; 3]; 3];
+        for (i, v) in values.into_iter().enumerate() {
+            matrix[i / 3][i % 3] = v;
+        }
+        Ok(Self(matrix))
+    }
+}
+
+impl fmt::Display for ColorMatrix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let values: Vec<String> = self.0.iter().flatten().map(f32::to_string).collect();
+        f.write_str(&values.join(","))
+    }
+}
+
+/// Square convolution kernel for `--kernel`, `width` values per row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Kernel {
+    pub values: Vec<f32>,
+    pub width: usize,
+}
+
+impl FromStr for Kernel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|v| v.trim().parse::<f32>().ok().filter(|v| v.is_finite()))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| format!("Expected comma separated numbers, got '{}'", s))?;
+        let width = (1..=values.len())
+            .find(|w| w * w >= values.len())
+            .filter(|w| w * w == values.len() && w % 2 == 1)
+            .ok_or_else(|| {
+                format!(
+                    "Expected 9, 25, 49... values for an odd sized square kernel, got {}",
+                    values.len()
+                )
+            })?;
+        Ok(Self { values, width })
+    }
+}
+
+impl fmt::Display for Kernel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let values: Vec<String> = self.values.iter().map(f32::to_string).collect();
+        f.write_str(&values.join(","))
+    }
+}
+
+/// `rrggbb:rrggbb:rrggbb` tints for `--color-balance`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorBalance {
+    pub shadows: (u8, u8, u8),
+    pub midtones: (u8, u8, u8),
+    pub highlights: (u8, u8, u8),
+}
+
+impl FromStr for ColorBalance {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let [shadows, midtones, highlights] = parts[..] else {
+            return Err(format!(
+                "Expected shadows:midtones:highlights colors, got '{}'",
+                s
+            ));
+        };
+        Ok(Self {
+            shadows: parse_rgb(shadows)?,
+            midtones: parse_rgb(midtones)?,
+            highlights: parse_rgb(highlights)?,
+        })
+    }
+}
+
+impl fmt::Display for ColorBalance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}",
+            Hex(self.shadows),
+            Hex(self.midtones),
+            Hex(self.highlights)
+        )
+    }
+}
+
+/// `from:to` in degrees for `--desaturate-except`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HueRange {
+    pub from: f32,
+    pub to: f32,
+}
+
+impl FromStr for HueRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hue = |h: &str| {
+            h.trim()
+                .parse::<f32>()
+                .ok()
+                .filter(|h| (0.0..=360.0).contains(h))
+        };
+        s.split_once(':')
+            .and_then(|(from, to)| Some((hue(from)?, hue(to)?)))
+            .map(|(from, to)| Self { from, to })
+            .ok_or_else(|| format!("Expected two hues in [0, 360] like 0:30, got '{}'", s))
+    }
+}
+
+impl fmt::Display for HueRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.from, self.to)
+    }
+}
+
+/// `cols,rows,rrggbb,opacity` for `--grid-overlay`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GridOverlay {
+    pub cols: NonZeroUsize,
+    pub rows: NonZeroUsize,
+    pub color: (u8, u8, u8),
+    pub opacity: f32,
+}
+
+impl FromStr for GridOverlay {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(',').map(str::trim).collect();
+        let [cols, rows, color, opacity] = parts[..] else {
+            return Err(format!("Expected cols,rows,color,opacity, got '{}'", s));
+        };
+        let count = |n: &str| {
+            n.parse()
+                .map_err(|_| format!("Expected a positive number of lines, got '{}'", n))
+        };
+        let opacity = opacity
+            .parse::<f32>()
+            .ok()
+            .filter(|o| (0.0..=1.0).contains(o))
+            .ok_or_else(|| format!("Expected an opacity in [0, 1], got '{}'", opacity))?;
+        Ok(Self {
+            cols: count(cols)?,
+            rows: count(rows)?,
+            color: parse_rgb(color)?,
+            opacity,
+        })
+    }
+}
+
+impl fmt::Display for GridOverlay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{},{},{},{}",
+            self.cols,
+            self.rows,
+            Hex(self.color),
+            self.opacity
+        )
+    }
+}
+
+/// `width:rrggbb` for `--icon-outline`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IconOutline {
+    pub width: NonZeroUsize,
+    pub color: (u8, u8, u8),
+}
+
+impl FromStr for IconOutline {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (width, color) = s
+            .split_once(':')
+            .ok_or_else(|| format!("Expected width:color, got '{}'", s))?;
+        let width = width
+            .parse()
+            .map_err(|_| format!("Expected an outline width in pixels, got '{}'", width))?;
+        Ok(Self {
+            width,
+            color: parse_rgb(color)?,
+        })
+    }
+}
+
+impl fmt::Display for IconOutline {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.width, Hex(self.color))
+    }
+}
+
+/// `channel:rrggbb` for `--dye`, `all` dyes by luma.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dye {
+    pub channel: Channel,
+    pub color: (u8, u8, u8),
+}
+
+impl FromStr for Dye {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (channel, color) = s
+            .split_once(':')
+            .ok_or_else(|| format!("Expected channel:color, got '{}'", s))?;
+        Ok(Self {
+            channel: channel.parse()?,
+            color: parse_rgb(color)?,
+        })
+    }
+}
+
+impl fmt::Display for Dye {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.channel, Hex(self.color))
+    }
+}
+
+/// `<width>x<height>` in pixels, for `--max-icon-size`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Size {
+    pub width: NonZeroUsize,
+    pub height: NonZeroUsize,
+}
+
+impl FromStr for Size {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split_once('x')
+            .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+            .map(|(width, height)| Self { width, height })
+            .ok_or_else(|| format!("Expected a size like 256x256, got '{}'", s))
+    }
+}
+
+impl fmt::Display for Size {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+/// A step of `--pipeline`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PipelineStep {
+    ScaleDown,
+    Blur,
+    ScaleUp,
+    Pixelate,
+    AutoContrast,
+    Brighten,
+    Darken,
+    ColorMatrix,
+    ColorBalance,
+    Desaturate,
+    Dye,
+    Kernel,
+    Quantize,
+    Mirror,
+    Shadow,
+    Overlay,
+}
+
+impl PipelineStep {
+    pub const VARIANTS: &'static [&'static str] = &[
+        "scale-down",
+        "blur",
+        "scale-up",
+        "pixelate",
+        "auto-contrast",
+        "brighten",
+        "darken",
+        "color-matrix",
+        "color-balance",
+        "desaturate",
+        "dye",
+        "kernel",
+        "quantize",
+        "mirror",
+        "shadow",
+        "overlay",
+    ];
+}
+
+impl FromStr for PipelineStep {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "scale-down" => Ok(Self::ScaleDown),
+            "blur" => Ok(Self::Blur),
+            "scale-up" => Ok(Self::ScaleUp),
+            "pixelate" => Ok(Self::Pixelate),
+            "auto-contrast" => Ok(Self::AutoContrast),
+            "brighten" => Ok(Self::Brighten),
+            "darken" => Ok(Self::Darken),
+            "color-matrix" => Ok(Self::ColorMatrix),
+            "color-balance" => Ok(Self::ColorBalance),
+            "desaturate" => Ok(Self::Desaturate),
+            "dye" => Ok(Self::Dye),
+            "kernel" => Ok(Self::Kernel),
+            "quantize" => Ok(Self::Quantize),
+            "mirror" => Ok(Self::Mirror),
+            "shadow" => Ok(Self::Shadow),
+            "overlay" => Ok(Self::Overlay),
+            _ => Err(format!("Unknown pipeline step: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for PipelineStep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::ScaleDown => "scale-down",
+            Self::Blur => "blur",
+            Self::ScaleUp => "scale-up",
+            Self::Pixelate => "pixelate",
+            Self::AutoContrast => "auto-contrast",
+            Self::Brighten => "brighten",
+            Self::Darken => "darken",
+            Self::ColorMatrix => "color-matrix",
+            Self::ColorBalance => "color-balance",
+            Self::Desaturate => "desaturate",
+            Self::Dye => "dye",
+            Self::Kernel => "kernel",
+            Self::Quantize => "quantize",
+            Self::Mirror => "mirror",
+            Self::Shadow => "shadow",
+            Self::Overlay => "overlay",
+        })
+    }
+}
+
+/// Parse a `major.minor` version, anything after the minor version is ignored.
+pub fn parse_version(s: &str) -> Result<(u32, u32), String> {
+    let mut parts = s.split('.');
+    let mut number = || {
+        parts
+            .next()
+            .map(|p| p.trim_end_matches(|c: char| !c.is_ascii_digit()))
+            .and_then(|p| p.parse().ok())
+    };
+    match (number(), number()) {
+        (Some(major), Some(minor)) => Ok((major, minor)),
+        _ => Err(format!("Expected a version like 2.13, got '{}'", s)),
+    }
+}
+
+/// Parse an `rrggbb` hex color.
+pub fn parse_rgb(s: &str) -> Result<(u8, u8, u8), String> {
+    let channel = |i: usize| {
+        s.get(i..i + 2)
+            .and_then(|c| u8::from_str_radix(c, 16).ok())
+            .ok_or_else(|| format!("Expected a color like ff8800, got '{}'", s))
+    };
+    if s.len() != 6 {
+        return Err(format!("Expected a color like ff8800, got '{}'", s));
+    }
+    Ok((channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// Formats a color the way `parse_rgb` reads it.
+struct Hex((u8, u8, u8));
+
+impl fmt::Display for Hex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (r, g, b) = self.0;
+        write!(f, "{:02x}{:02x}{:02x}", r, g, b)
+    }
+}
+
+/// `size:rrggbb:rrggbb` for `--checkerboard`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkerboard {
+    pub size: NonZeroUsize,
+    pub colors: [(u8, u8, u8); 2],
+}
+
+impl FromStr for Checkerboard {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let [size, a, b] = parts[..] else {
+            return Err(format!("Expected size:color:color, got '{}'", s));
+        };
+        let size = size
+            .parse()
+            .map_err(|_| format!("Expected a square size in pixels, got '{}'", size))?;
+        Ok(Self {
+            size,
+            colors: [parse_rgb(a)?, parse_rgb(b)?],
+        })
+    }
+}
+
+impl fmt::Display for Checkerboard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [a, b] = self.colors;
+        write!(f, "{}:{}:{}", self.size, Hex(a), Hex(b))
+    }
+}
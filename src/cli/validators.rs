@@ -5,3 +5,24 @@ pub fn has_compose(_: String) -> Result<(), String> {
         Err("Must have 'png' or 'jpeg' features enabled to compose images".to_owned())
     }
 }
+
+pub fn is_positive(s: String) -> Result<(), String> {
+    match s.parse::<f32>() {
+        Ok(f) if f.is_finite() && f > 0.0 => Ok(()),
+        _ => Err(format!("Expected a positive number, got '{}'", s)),
+    }
+}
+
+pub fn is_fraction(s: String) -> Result<(), String> {
+    match s.parse::<f32>() {
+        Ok(f) if (0.0..=1.0).contains(&f) => Ok(()),
+        _ => Err(format!("Expected a number in [0, 1], got '{}'", s)),
+    }
+}
+
+pub fn is_finite(s: String) -> Result<(), String> {
+    match s.parse::<f32>() {
+        Ok(f) if f.is_finite() => Ok(()),
+        _ => Err(format!("Expected a number, got '{}'", s)),
+    }
+}
@@ -0,0 +1,25 @@
+use std::error::Error;
+use std::io;
+use std::time::Duration;
+
+use imagefmt::ColFmt;
+
+use x11_clipboard::Clipboard;
+
+/// Give up if the clipboard owner hasn't answered by then.
+const TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Decode the PNG image in the X clipboard.
+pub fn read_image() -> Result<imagefmt::Image<u8>, Box<dyn Error>> {
+    let clipboard = Clipboard::new()?;
+    let atoms = &clipboard.getter.atoms;
+    let png = clipboard.getter.get_atom("image/png")?;
+    let bytes = clipboard.load(atoms.clipboard, png, atoms.property, TIMEOUT)?;
+    if bytes.is_empty() {
+        return Err("The clipboard doesn't contain a PNG image".into());
+    }
+    Ok(imagefmt::read_from(
+        &mut io::Cursor::new(bytes),
+        ColFmt::BGRA,
+    )?)
+}
@@ -0,0 +1,428 @@
+use imgref::ImgRefMut;
+
+#[cfg(feature = "threads")]
+use rayon::prelude::*;
+
+use rgb::alt::BGRA8;
+
+use crate::cli::{Channel, Dye};
+
+/// Multiply each pixel's `[r, g, b]` by the row-major `matrix`, alpha is left alone.
+pub fn apply_color_matrix(img: &mut ImgRefMut<BGRA8>, matrix: [[f32; 3]; 3]) {
+    let transform = |px: &mut BGRA8| {
+        let rgb = [f32::from(px.r), f32::from(px.g), f32::from(px.b)];
+        let [r, g, b] = matrix.map(|row| {
+            let c: f32 = row.iter().zip(rgb).map(|(m, c)| m * c).sum();
+            c.round().clamp(0.0, 255.0) as u8
+        });
+        *px = BGRA8 { b, g, r, a: px.a };
+    };
+
+    #[cfg(not(feature = "threads"))]
+    img.pixels_mut().for_each(transform);
+
+    #[cfg(feature = "threads")]
+    img.rows_mut()
+        .par_bridge()
+        .for_each(|row| row.iter_mut().for_each(&transform));
+}
+
+/// Stretch the 1st to 99th percentile of luma over the full range, the same for each channel.
+/// Alpha is left alone, as are images with (almost) a single brightness.
+pub fn auto_contrast(img: &mut ImgRefMut<BGRA8>) {
+    let mut histogram = [0_usize; 256];
+    for px in img.rows().flatten() {
+        let luma =
+            (299 * u32::from(px.r) + 587 * u32::from(px.g) + 114 * u32::from(px.b) + 500) / 1000;
+        histogram[luma as usize] += 1;
+    }
+
+    let total: usize = histogram.iter().sum();
+    let percentile = |p: usize| {
+        let mut seen = 0;
+        histogram
+            .iter()
+            .position(|&n| {
+                seen += n;
+                seen * 100 >= total * p
+            })
+            .unwrap_or(255)
+    };
+    let (low, high) = (percentile(1), percentile(99));
+    if high <= low {
+        return;
+    }
+
+    let range = high - low;
+    let mut table = [0_u8; 256];
+    for (c, out) in table.iter_mut().enumerate() {
+        let c = c.clamp(low, high) - low;
+        *out = ((c * 255 + range / 2) / range) as u8;
+    }
+
+    let transform = |px: &mut BGRA8| {
+        px.r = table[usize::from(px.r)];
+        px.g = table[usize::from(px.g)];
+        px.b = table[usize::from(px.b)];
+    };
+
+    #[cfg(not(feature = "threads"))]
+    img.pixels_mut().for_each(transform);
+
+    #[cfg(feature = "threads")]
+    img.rows_mut()
+        .par_bridge()
+        .for_each(|row| row.iter_mut().for_each(&transform));
+}
+
+/// Shift shadows, midtones and highlights towards their own `(r, g, b)` tint.
+///
+/// `808080` leaves a range alone, every channel is moved by how far its value is from 128.
+/// Pixels are weighted between the ranges by their luma, fading smoothly from one to the next.
+pub fn color_balance(
+    img: &mut ImgRefMut<BGRA8>,
+    shadows: (u8, u8, u8),
+    midtones: (u8, u8, u8),
+    highlights: (u8, u8, u8),
+) {
+    let shift = |(r, g, b): (u8, u8, u8)| [r, g, b].map(|c| f32::from(c) - 128.0);
+    let ranges = [shift(shadows), shift(midtones), shift(highlights)];
+    let smoothstep = |from: f32, to: f32, x: f32| {
+        let t = ((x - from) / (to - from)).clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    };
+
+    let transform = |px: &mut BGRA8| {
+        let luma =
+            (0.299 * f32::from(px.r) + 0.587 * f32::from(px.g) + 0.114 * f32::from(px.b)) / 255.0;
+        let shadow = 1.0 - smoothstep(0.0, 0.5, luma);
+        let highlight = smoothstep(0.5, 1.0, luma);
+        let weights = [shadow, 1.0 - shadow - highlight, highlight];
+
+        let [r, g, b] = [px.r, px.g, px.b].map(f32::from);
+        let mut rgb = [r, g, b];
+        for (range, weight) in ranges.iter().zip(weights) {
+            for (c, s) in rgb.iter_mut().zip(range) {
+                *c += s * weight;
+            }
+        }
+        let [r, g, b] = rgb.map(|c| c.round().clamp(0.0, 255.0) as u8);
+        *px = BGRA8 { b, g, r, a: px.a };
+    };
+
+    #[cfg(not(feature = "threads"))]
+    img.pixels_mut().for_each(transform);
+
+    #[cfg(feature = "threads")]
+    img.rows_mut()
+        .par_bridge()
+        .for_each(|row| row.iter_mut().for_each(&transform));
+}
+
+/// Reduce the image to at most `n` colors picked by median cut, alpha is left alone.
+///
+/// The box of colors spanning the widest range in any channel is split at its median
+/// until there are `n` boxes, then every pixel is replaced by the nearest box average.
+#[cfg(feature = "quantize")]
+pub fn quantize(img: &mut ImgRefMut<BGRA8>, n: usize) {
+    let mut colors: Vec<[u8; 3]> = img.rows().flatten().map(|px| [px.r, px.g, px.b]).collect();
+
+    let mut boxes = vec![(0..colors.len(), widest_channel(&colors))];
+    while boxes.len() < n {
+        let Some((i, &(_, (channel, spread)))) = boxes
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, (_, (_, spread)))| *spread)
+        else {
+            break;
+        };
+        if spread == 0 {
+            break;
+        }
+
+        let (range, _) = boxes.swap_remove(i);
+        colors[range.clone()].sort_unstable_by_key(|c| c[channel]);
+        let mid = range.start + range.len() / 2;
+        for half in [range.start..mid, mid..range.end] {
+            let widest = widest_channel(&colors[half.clone()]);
+            boxes.push((half, widest));
+        }
+    }
+
+    let palette: Vec<[u8; 3]> = boxes
+        .into_iter()
+        .filter(|(range, _)| !range.is_empty())
+        .map(|(range, _)| {
+            let len = range.len() as u64;
+            let sum = colors[range].iter().fold([0_u64; 3], |mut sum, c| {
+                sum.iter_mut().zip(c).for_each(|(s, &c)| *s += u64::from(c));
+                sum
+            });
+            sum.map(|s| ((s + len / 2) / len) as u8)
+        })
+        .collect();
+
+    let transform = |px: &mut BGRA8| {
+        let distance = |c: &&[u8; 3]| {
+            [px.r, px.g, px.b]
+                .iter()
+                .zip(*c)
+                .map(|(&a, &b)| (i32::from(a) - i32::from(b)).pow(2))
+                .sum::<i32>()
+        };
+        if let Some(&[r, g, b]) = palette.iter().min_by_key(distance) {
+            *px = BGRA8 { b, g, r, a: px.a };
+        }
+    };
+
+    #[cfg(not(feature = "threads"))]
+    img.pixels_mut().for_each(transform);
+
+    #[cfg(feature = "threads")]
+    img.rows_mut()
+        .par_bridge()
+        .for_each(|row| row.iter_mut().for_each(&transform));
+}
+
+/// The channel with the widest range of values in `colors`, and that range.
+#[cfg(feature = "quantize")]
+fn widest_channel(colors: &[[u8; 3]]) -> (usize, u8) {
+    (0..3)
+        .map(|channel| {
+            let (min, max) = colors.iter().fold((u8::MAX, u8::MIN), |(min, max), c| {
+                (min.min(c[channel]), max.max(c[channel]))
+            });
+            (channel, max.saturating_sub(min))
+        })
+        .max_by_key(|&(_, spread)| spread)
+        .unwrap_or_default()
+}
+
+/// Hue in degrees, `None` for grays.
+fn hue(px: BGRA8) -> Option<f32> {
+    let (r, g, b) = (f32::from(px.r), f32::from(px.g), f32::from(px.b));
+    let max = r.max(g).max(b);
+    let delta = max - r.min(g).min(b);
+    if delta == 0.0 {
+        return None;
+    }
+    let h = if max == r {
+        ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        (b - r) / delta + 2.0
+    } else {
+        (r - g) / delta + 4.0
+    };
+    Some(h * 60.0)
+}
+
+/// Turn every pixel whose hue lies outside `from..=to` degrees gray, keeping its HSL lightness.
+/// The range wraps around if `from` is greater than `to`, e.g. 330 to 30 for reds.
+pub fn desaturate_except(img: &mut ImgRefMut<BGRA8>, from: f32, to: f32) {
+    let keep = |h: f32| {
+        if from <= to {
+            (from..=to).contains(&h)
+        } else {
+            h >= from || h <= to
+        }
+    };
+    let transform = |px: &mut BGRA8| {
+        if hue(*px).is_some_and(|h| !keep(h)) {
+            let max = px.r.max(px.g).max(px.b);
+            let min = px.r.min(px.g).min(px.b);
+            let l = ((u16::from(max) + u16::from(min) + 1) / 2) as u8;
+            *px = BGRA8 {
+                b: l,
+                g: l,
+                r: l,
+                a: px.a,
+            };
+        }
+    };
+
+    #[cfg(not(feature = "threads"))]
+    img.pixels_mut().for_each(transform);
+
+    #[cfg(feature = "threads")]
+    img.rows_mut()
+        .par_bridge()
+        .for_each(|row| row.iter_mut().for_each(&transform));
+}
+
+/// Replace each pixel with the sum of the `dyes`' colors, each scaled by its channel's value.
+/// Alpha is left alone.
+pub fn dye(img: &mut ImgRefMut<BGRA8>, dyes: &[Dye]) {
+    let transform = |px: &mut BGRA8| {
+        let mut sum = [0_u32; 3];
+        for dye in dyes {
+            let value = match dye.channel {
+                Channel::R => u32::from(px.r),
+                Channel::G => u32::from(px.g),
+                Channel::B => u32::from(px.b),
+                Channel::All => {
+                    (299 * u32::from(px.r) + 587 * u32::from(px.g) + 114 * u32::from(px.b) + 500)
+                        / 1000
+                }
+            };
+            let (r, g, b) = dye.color;
+            for (s, c) in sum.iter_mut().zip([r, g, b]) {
+                *s += value * u32::from(c);
+            }
+        }
+        let [r, g, b] = sum.map(|s| ((s + 127) / 255).min(255) as u8);
+        *px = BGRA8 { b, g, r, a: px.a };
+    };
+
+    #[cfg(not(feature = "threads"))]
+    img.pixels_mut().for_each(transform);
+
+    #[cfg(feature = "threads")]
+    img.rows_mut()
+        .par_bridge()
+        .for_each(|row| row.iter_mut().for_each(&transform));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color_matrix() {
+        let mut data = [BGRA8 {
+            b: 30,
+            g: 20,
+            r: 10,
+            a: 128,
+        }];
+        let original = data[0];
+        let mut img = ImgRefMut::new(&mut data, 1, 1);
+
+        apply_color_matrix(
+            &mut img,
+            [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+        );
+        assert_eq!(img[(0, 0)], original);
+
+        // swap red and blue, then blow out green
+        let mut img = ImgRefMut::new(&mut data, 1, 1);
+        apply_color_matrix(
+            &mut img,
+            [[0.0, 0.0, 1.0], [0.0, 20.0, 0.0], [1.0, 0.0, 0.0]],
+        );
+        assert_eq!(
+            data[0],
+            BGRA8 {
+                b: 10,
+                g: 255,
+                r: 30,
+                a: 128,
+            }
+        );
+    }
+
+    #[test]
+    fn auto_contrast() {
+        let mut data: Vec<BGRA8> = (100..200)
+            .map(|v| BGRA8 {
+                b: v,
+                g: v,
+                r: v,
+                a: 7,
+            })
+            .collect();
+        let mut img = ImgRefMut::new(&mut data, 10, 10);
+        super::auto_contrast(&mut img);
+        assert_eq!(
+            [data[0].r, data[49].g, data[98].b, data[99].r],
+            [0, 128, 255, 255]
+        );
+        assert!(data.iter().all(|px| px.a == 7));
+
+        // a flat image is left alone
+        let mut data = [BGRA8 {
+            b: 9,
+            g: 9,
+            r: 9,
+            a: 255,
+        }; 4];
+        super::auto_contrast(&mut ImgRefMut::new(&mut data, 2, 2));
+        assert_eq!(data[0].r, 9);
+    }
+
+    #[test]
+    fn color_balance() {
+        let px = |r, g, b| BGRA8 { b, g, r, a: 255 };
+        let mut data = [px(0, 0, 0), px(128, 128, 128), px(255, 255, 255)];
+        let mut img = ImgRefMut::new(&mut data, 3, 1);
+        super::color_balance(&mut img, (128, 128, 148), (128, 128, 128), (108, 128, 128));
+        assert_eq!(data, [px(0, 0, 20), px(128, 128, 128), px(235, 255, 255)]);
+    }
+
+    #[cfg(feature = "quantize")]
+    #[test]
+    fn quantize() {
+        let px = |r, g, b| BGRA8 { b, g, r, a: 255 };
+        let mut data = [
+            px(0, 0, 0),
+            px(250, 250, 250),
+            px(10, 10, 10),
+            px(240, 240, 240),
+        ];
+        let mut img = ImgRefMut::new(&mut data, 4, 1);
+        super::quantize(&mut img, 2);
+        assert_eq!(
+            data,
+            [
+                px(5, 5, 5),
+                px(245, 245, 245),
+                px(5, 5, 5),
+                px(245, 245, 245)
+            ]
+        );
+
+        // fewer colors than asked for stay put
+        let original = [px(1, 2, 3), px(200, 0, 9)];
+        let mut data = original;
+        let mut img = ImgRefMut::new(&mut data, 2, 1);
+        super::quantize(&mut img, 16);
+        assert_eq!(data, original);
+    }
+
+    #[test]
+    fn dye() {
+        let px = |r, g, b| BGRA8 { b, g, r, a: 255 };
+        let dyed = |dyes: &str| {
+            let dyes: Vec<Dye> = dyes.split(',').map(|d| d.parse().unwrap()).collect();
+            let mut data = [px(200, 100, 50)];
+            super::dye(&mut ImgRefMut::new(&mut data, 1, 1), &dyes);
+            data[0]
+        };
+        assert_eq!(dyed("r:ff0000"), px(200, 0, 0));
+        assert_eq!(dyed("r:ff0000,g:00ff00"), px(200, 100, 0));
+        assert_eq!(dyed("b:ffffff"), px(50, 50, 50));
+        assert_eq!(dyed("all:ffffff"), px(124, 124, 124));
+        assert_eq!(dyed("r:ffffff,g:ffffff"), px(255, 255, 255));
+    }
+
+    #[test]
+    fn desaturate_except() {
+        let px = |r, g, b| BGRA8 { b, g, r, a: 255 };
+        let mut data = [
+            px(200, 20, 20),
+            px(20, 200, 20),
+            px(255, 0, 64),
+            px(90, 90, 90),
+        ];
+        let mut img = ImgRefMut::new(&mut data, 4, 1);
+        super::desaturate_except(&mut img, 330.0, 30.0);
+        assert_eq!(
+            data,
+            [
+                px(200, 20, 20),
+                px(110, 110, 110),
+                px(255, 0, 64),
+                px(90, 90, 90)
+            ]
+        );
+    }
+}
@@ -0,0 +1,514 @@
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::error::Error;
+use std::ffi::OsString;
+use std::fs;
+use std::io::ErrorKind::NotFound;
+use std::iter;
+use std::num::{NonZeroU64, NonZeroU8, NonZeroUsize};
+use std::path::PathBuf;
+
+use log::debug;
+
+use serde::Deserialize;
+
+use structopt::clap::ErrorKind;
+use structopt::StructOpt;
+
+use toml::{Table, Value};
+
+use crate::cli::{
+    Channel, Checkerboard, Cli, ColorBalance, ColorMatrix, Dye, FlipMode, GridOverlay, HueRange,
+    IconOutline, Kernel, LogLevel, MonitorOrder, OutputFormat, PipelineStep, PixelFormat,
+    ScaleQuality, ScreenshotMethod, Size, StdinFormat,
+};
+
+/// The config file. Keys are long option names and take the same values
+/// as on the command line, lists may be given as arrays.
+///
+/// ```toml
+/// blur = 10
+/// position = [945, -20]
+/// i3lock = ["--nofork", "--ignore-empty-password"]
+///
+/// [profile.work]
+/// icon = "/usr/share/pixmaps/company.png"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+pub struct Config {
+    /// Named sets of options, picked with `--profile`.
+    #[serde(default, rename = "profile")]
+    pub profiles: HashMap<String, Config>,
+
+    #[serde(flatten)]
+    options: BTreeMap<String, Value>,
+}
+
+impl Config {
+    /// Convert to command line arguments, i3lock's arguments are returned separately.
+    fn to_args(&self) -> Result<(Vec<OsString>, Vec<OsString>), String> {
+        let mut args = Vec::new();
+        let mut i3lock = Vec::new();
+
+        for (key, value) in &self.options {
+            match value {
+                Value::Array(values) if key == "i3lock" => {
+                    for v in values {
+                        i3lock.push(scalar(key, v)?.into());
+                    }
+                }
+                _ if key == "i3lock" => i3lock.push(scalar(key, value)?.into()),
+                Value::Boolean(true) => args.push(format!("--{}", key).into()),
+                Value::Boolean(false) => {}
+                Value::Array(values) => {
+                    let values = values
+                        .iter()
+                        .map(|v| scalar(key, v))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    args.push(format!("--{}={}", key, values.join(",")).into());
+                }
+                _ => args.push(format!("--{}={}", key, scalar(key, value)?).into()),
+            }
+        }
+
+        Ok((args, i3lock))
+    }
+}
+
+fn scalar(key: &str, value: &Value) -> Result<String, String> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Integer(i) => Ok(i.to_string()),
+        Value::Float(f) => Ok(f.to_string()),
+        Value::Boolean(b) => Ok(b.to_string()),
+        _ => Err(format!("Unsupported value for \"{}\" in config file", key)),
+    }
+}
+
+/// `$XDG_CONFIG_HOME/i3lockr/config.toml`
+pub fn default_path() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))
+        .map(|dir| dir.join(env!("CARGO_PKG_NAME")).join("config.toml"))
+}
+
+/// Parse the command line again with the config file's options in front of it,
+/// so that anything given on the command line wins. Options that conflict with
+/// the command line are left out.
+pub fn apply(args: Cli) -> Result<Cli, Box<dyn Error>> {
+    match argv(&args)? {
+        Some(argv) => Ok(Cli::from_iter(argv)),
+        None => Ok(args),
+    }
+}
+
+/// The command line with the config file's options in front of it,
+/// `None` if there is no config file.
+fn argv(args: &Cli) -> Result<Option<Vec<OsString>>, Box<dyn Error>> {
+    let path = match args.config.clone().or_else(default_path) {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        // not having a config file is fine, unless we were asked for one
+        Err(e) if e.kind() == NotFound && args.config.is_none() && args.profile.is_none() => {
+            return Ok(None)
+        }
+        Err(e) => return Err(format!("{}: {}", path.display(), e).into()),
+    };
+    let mut config: Config =
+        toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    // merge the values rather than the arguments, so a profile can set a flag back to false
+    if let Some(ref name) = args.profile {
+        let profile = config
+            .profiles
+            .remove(name)
+            .ok_or_else(|| format!("No profile named \"{}\" in {}", name, path.display()))?;
+        config.options.extend(profile.options);
+    }
+    let (options, i3lock) = config.to_args()?;
+
+    let cli: Vec<OsString> = env::args_os().collect();
+    let options = without_conflicts(options, &cli);
+    let mut argv = cli.into_iter();
+    let mut argv: Vec<OsString> = argv.next().into_iter().chain(options).chain(argv).collect();
+
+    // i3lock arguments on the command line replace the config file's
+    if args.i3lock.is_empty() && !i3lock.is_empty() {
+        if !argv.iter().any(|a| a == "--") {
+            argv.push("--".into());
+        }
+        argv.extend(i3lock);
+    }
+
+    Ok(Some(argv))
+}
+
+/// `options` that clap doesn't reject as conflicting with the command line `cli`.
+fn without_conflicts(options: Vec<OsString>, cli: &[OsString]) -> Vec<OsString> {
+    let (bin, rest) = cli.split_at(cli.len().min(1));
+    options
+        .into_iter()
+        .filter(|option| {
+            let argv = bin.iter().chain(iter::once(option)).chain(rest);
+            match Cli::clap().get_matches_from_safe(argv) {
+                Err(e) if e.kind == ErrorKind::ArgumentConflict => {
+                    debug!("Ignoring {:?} from the config file: {}", option, e.message);
+                    false
+                }
+                _ => true,
+            }
+        })
+        .collect()
+}
+
+/// The options in effect, config file and defaults included, as a config file.
+pub fn export(args: Cli) -> Result<String, Box<dyn Error>> {
+    to_toml(&apply(args)?)
+}
+
+/// `args` as a config file. Options left at their defaults are listed commented out,
+/// they could conflict with or require other options if set explicitly.
+fn to_toml(args: &Cli) -> Result<String, Box<dyn Error>> {
+    let defaults = options(&Cli::from_iter(iter::once(env!("CARGO_PKG_NAME"))));
+
+    let mut table = Table::new();
+    let mut unchanged = Table::new();
+    for (key, value) in options(args) {
+        let target = match defaults.get(&key) {
+            Some(default) if *default == value => &mut unchanged,
+            _ => &mut table,
+        };
+        target.insert(key, value);
+    }
+
+    let mut text = toml::to_string(&table)?;
+    if !unchanged.is_empty() {
+        text.push_str("\n# defaults\n");
+        for line in toml::to_string(&unchanged)?.lines() {
+            text.push_str("# ");
+            text.push_str(line);
+            text.push('\n');
+        }
+    }
+    Ok(text)
+}
+
+/// Every option set in `args`, keyed by its long name.
+/// `--config`, `--profile` and `--export-config` were already taken into account.
+fn options(args: &Cli) -> Table {
+    let mut table = Table::new();
+    macro_rules! insert {
+        ($($key:literal => $field:ident,)*) => {
+            $(
+                if let Some(value) = args.$field.to_value() {
+                    table.insert($key.to_owned(), value);
+                }
+            )*
+        };
+    }
+    insert! {
+        "verbose" => verbose,
+        "no-color" => no_color,
+        "quiet" => quiet,
+        "log-level" => log_level,
+        "verbose-stages" => verbose_stages,
+        "fail-on-no-display" => fail_on_no_display,
+        "read-image-stdin" => read_image_stdin,
+        "stdin-image-format" => stdin_image_format,
+        "stdin-width" => stdin_width,
+        "stdin-height" => stdin_height,
+        "raw-input" => raw_input,
+        "raw-width" => raw_width,
+        "raw-height" => raw_height,
+        "checkerboard" => checkerboard,
+        "background-image" => background_image,
+        "scale-quality" => scale_quality,
+        "max-memory" => max_memory,
+        "screenshot-method" => screenshot_method,
+        "screenshot-delay-frames" => screenshot_delay_frames,
+        "self-test" => self_test,
+        "format-check" => format_check,
+        "benchmark-effects" => benchmark_effects,
+        "width" => width,
+        "height" => height,
+        "darken" => dark,
+        "brighten" => bright,
+        "channel" => channel,
+        "blur" => radius,
+        "gaussian-sigma" => gaussian_sigma,
+        "blur-alpha" => blur_alpha,
+        "blur-alpha-radius" => blur_alpha_radius,
+        "background-blur" => background_blur,
+        "background-clear-size" => background_clear_size,
+        "max-blur-radius-override" => max_blur_radius_override,
+        "adaptive-blur" => adaptive_blur,
+        "detect-retina" => detect_retina,
+        "dpi-threshold" => dpi_threshold,
+        "scale" => factor,
+        "scale-down-only" => scale_down_only,
+        "scale-up-only" => scale_up_only,
+        "color-matrix" => color_matrix,
+        "auto-contrast" => auto_contrast,
+        "color-balance" => color_balance,
+        "color-quantize" => color_quantize,
+        "kernel" => kernel,
+        "kernel-normalize" => kernel_normalize,
+        "desaturate-except" => desaturate_except,
+        "dye" => dye,
+        "pixelate" => pixelate,
+        "no-effects" => no_effects,
+        "pipeline" => pipeline,
+        "title-bar-crop" => title_bar_crop,
+        "shadow-overlay" => shadow_overlay,
+        "xrandr-fallback" => xrandr_fallback,
+        "detect-displays-retry" => detect_displays_retry,
+        "xinerama-mode" => xinerama_mode,
+        "grid-overlay" => grid_overlay,
+        "watermark" => watermark,
+        "watermark-color" => watermark_color,
+        "watermark-opacity" => watermark_opacity,
+        "watermark-size" => watermark_size,
+        "watermark-font" => watermark_font,
+        "monitor-info-json" => monitor_info_json,
+        "debug-save-stages" => debug_save_stages,
+        "debug-monitors" => debug_monitors,
+        "ignore-monitors" => ignore,
+        "ignore-monitors-named" => ignore_named,
+        "mirror-x" => mirror_x,
+        "mirror-y" => mirror_y,
+        "monitor-order" => monitor_order,
+        "clamp-icon-to-monitor" => clamp_icon_to_monitor,
+        "invert" => invert,
+        "position" => pos,
+        "icon-repeat-diagonal" => icon_repeat_diagonal,
+        "force-large-icon" => force_large_icon,
+        "center-on-cursor" => center_on_cursor,
+        "icon" => path,
+        "icon-from-clipboard" => icon_from_clipboard,
+        "pad-icon" => pad_icon,
+        "icon-scale" => icon_scale,
+        "icon-blend-behind" => icon_blend_behind,
+        "icon-mask" => icon_mask,
+        "icon-alpha-multiply" => icon_alpha_multiply,
+        "icon-colorize" => icon_colorize,
+        "icon-outline" => icon_outline,
+        "icon-flip" => icon_flip,
+        "icon-rotate" => icon_rotate,
+        "max-icon-size" => max_icon_size,
+        "premultiplied" => premultiplied,
+        "icon-cache-ttl" => icon_cache_ttl,
+        "lock-after" => lock_after,
+        "notify-before-lock" => notify_before_lock,
+        "delay-lock" => delay_lock,
+        "pam-auth-timeout" => pam_auth_timeout,
+        "verbose-i3lock" => verbose_i3lock,
+        "after-lock" => after_lock,
+        "preview" => preview,
+        "apply-to-root" => apply_to_root,
+        "output-png" => output_png,
+        "scrot-compat" => scrot_compat,
+        "output-bmp" => output_bmp,
+        "stdout" => stdout,
+        "stdout-format" => stdout_format,
+        "format-raw-header" => format_raw_header,
+        "lockscreen-only-on" => lockscreen_only_on,
+        "pixel-format" => pixel_format,
+        "strip-alpha" => strip_alpha,
+        "xdg-runtime-lock" => xdg_runtime_lock,
+        "version-pin" => version_pin,
+        "skip-i3lock-check" => skip_i3lock_check,
+        "compress-output" => compress_output,
+        "i3lock-supports-gzip" => i3lock_supports_gzip,
+        "retry-on-wrong-password" => retry_on_wrong_password,
+        "i3lock-args-file" => i3lock_args_file,
+        "i3lock" => i3lock,
+    }
+    table
+}
+
+/// An option as a config file value, `None` if it isn't set.
+trait ToValue {
+    fn to_value(&self) -> Option<Value>;
+}
+
+impl ToValue for bool {
+    fn to_value(&self) -> Option<Value> {
+        self.then_some(Value::Boolean(true))
+    }
+}
+
+impl<T: ToValue> ToValue for Option<T> {
+    fn to_value(&self) -> Option<Value> {
+        self.as_ref().and_then(ToValue::to_value)
+    }
+}
+
+impl<T: ToValue> ToValue for Vec<T> {
+    fn to_value(&self) -> Option<Value> {
+        let values: Vec<Value> = self.iter().filter_map(ToValue::to_value).collect();
+        (!values.is_empty()).then_some(Value::Array(values))
+    }
+}
+
+impl ToValue for PathBuf {
+    fn to_value(&self) -> Option<Value> {
+        Some(Value::String(self.to_string_lossy().into_owned()))
+    }
+}
+
+impl ToValue for OsString {
+    fn to_value(&self) -> Option<Value> {
+        Some(Value::String(self.to_string_lossy().into_owned()))
+    }
+}
+
+/// An `rrggbb` color.
+impl ToValue for (u8, u8, u8) {
+    fn to_value(&self) -> Option<Value> {
+        let (r, g, b) = self;
+        Some(Value::String(format!("{:02x}{:02x}{:02x}", r, g, b)))
+    }
+}
+
+/// A `major.minor` version, kept a string so 2.10 doesn't turn into 2.1.
+impl ToValue for (u32, u32) {
+    fn to_value(&self) -> Option<Value> {
+        Some(Value::String(format!("{}.{}", self.0, self.1)))
+    }
+}
+
+/// Numbers, as integers where they are whole.
+macro_rules! typed_value {
+    ($($t:ty),*) => {
+        $(
+            impl ToValue for $t {
+                fn to_value(&self) -> Option<Value> {
+                    Some(typed(&self.to_string()))
+                }
+            }
+        )*
+    };
+}
+
+typed_value!(u8, u64, usize, f32, NonZeroU8, NonZeroU64, NonZeroUsize);
+
+/// Text and values with their own syntax, always strings so e.g. colors stay hex.
+macro_rules! display_value {
+    ($($t:ty),*) => {
+        $(
+            impl ToValue for $t {
+                fn to_value(&self) -> Option<Value> {
+                    Some(Value::String(self.to_string()))
+                }
+            }
+        )*
+    };
+}
+
+display_value!(
+    Channel,
+    Checkerboard,
+    ColorBalance,
+    ColorMatrix,
+    Dye,
+    FlipMode,
+    GridOverlay,
+    HueRange,
+    IconOutline,
+    Kernel,
+    LogLevel,
+    MonitorOrder,
+    OutputFormat,
+    PipelineStep,
+    PixelFormat,
+    ScaleQuality,
+    ScreenshotMethod,
+    Size,
+    StdinFormat,
+    String
+);
+
+/// A command line value as the TOML value `scalar` turns back into it.
+fn typed(value: &str) -> Value {
+    if let Ok(i) = value.parse() {
+        Value::Integer(i)
+    } else if let Some(f) = value.parse().ok().filter(|f: &f64| f.is_finite()) {
+        Value::Float(f)
+    } else {
+        Value::String(value.to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn args() {
+        let config: Config = toml::from_str(
+            r#"
+            blur = 10
+            invert = true
+            clamp-icon-to-monitor = false
+            position = [945, -20]
+            i3lock = ["--nofork"]
+
+            [profile.work]
+            icon = "work.png"
+            "#,
+        )
+        .unwrap();
+
+        let (args, i3lock) = config.to_args().unwrap();
+        assert_eq!(args, ["--blur=10", "--invert", "--position=945,-20"]);
+        assert_eq!(i3lock, ["--nofork"]);
+
+        let (args, i3lock) = config.profiles["work"].to_args().unwrap();
+        assert_eq!(args, ["--icon=work.png"]);
+        assert!(i3lock.is_empty());
+    }
+
+    #[test]
+    fn export() {
+        let args = Cli::from_iter([
+            "i3lockr",
+            "-b",
+            "10",
+            "--checkerboard=16:000000:ffffff",
+            "--invert",
+            "-u",
+            "945,-20",
+            "--version-pin=2.10",
+            "--",
+            "--nofork",
+        ]);
+        let text = to_toml(&args).unwrap();
+        let config: Config = toml::from_str(&text).unwrap();
+
+        let (args, i3lock) = config.to_args().unwrap();
+        assert_eq!(
+            args,
+            [
+                "--blur=10",
+                "--checkerboard=16:000000:ffffff",
+                "--invert",
+                "--position=945,-20",
+                "--version-pin=2.10"
+            ]
+        );
+        assert_eq!(i3lock, ["--nofork"]);
+        assert!(text.contains("\n# channel = \"all\"\n"));
+    }
+
+    #[test]
+    fn conflicts() {
+        let options = ["--icon-repeat-diagonal=3", "--blur=10"].map(OsString::from);
+        let cli = ["i3lockr", "--position=945,-20"].map(OsString::from);
+        assert_eq!(without_conflicts(options.to_vec(), &cli), ["--blur=10"]);
+    }
+}
@@ -0,0 +1,103 @@
+use imgref::ImgRefMut;
+
+#[cfg(feature = "threads")]
+use rayon::prelude::*;
+
+use rgb::alt::BGRA8;
+
+/// Scale `kernel` so that it sums to 1, kernels that sum to 0 (edge detection) are left alone.
+pub fn normalize(kernel: &mut [f32]) {
+    let sum: f32 = kernel.iter().sum();
+    if sum != 0.0 {
+        kernel.iter_mut().for_each(|k| *k /= sum);
+    }
+}
+
+/// Convolve `img` with the square `kernel`, which is `width` values wide and must be odd sized.
+/// Pixels past the edges repeat the edge, alpha is left alone.
+pub fn convolve(img: &mut ImgRefMut<BGRA8>, kernel: &[f32], width: usize) {
+    debug_assert_eq!(kernel.len(), width * width);
+    let (w, h) = (img.width(), img.height());
+    let src: Vec<BGRA8> = img.rows().flat_map(|row| row.iter().copied()).collect();
+    let half = (width / 2) as isize;
+
+    let filter_row = |(y, row): (usize, &mut [BGRA8])| {
+        for (x, px) in row.iter_mut().enumerate() {
+            let (mut b, mut g, mut r) = (0.0, 0.0, 0.0);
+            for (i, k) in kernel.iter().enumerate() {
+                let sx = (x as isize + (i % width) as isize - half).clamp(0, w as isize - 1);
+                let sy = (y as isize + (i / width) as isize - half).clamp(0, h as isize - 1);
+                let p = src[sy as usize * w + sx as usize];
+                b += k * f32::from(p.b);
+                g += k * f32::from(p.g);
+                r += k * f32::from(p.r);
+            }
+            let c = |v: f32| v.round().clamp(0.0, 255.0) as u8;
+            *px = BGRA8 {
+                b: c(b),
+                g: c(g),
+                r: c(r),
+                a: px.a,
+            };
+        }
+    };
+
+    #[cfg(not(feature = "threads"))]
+    img.rows_mut().enumerate().for_each(filter_row);
+
+    #[cfg(feature = "threads")]
+    img.rows_mut().enumerate().par_bridge().for_each(filter_row);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: BGRA8 = BGRA8 {
+        b: 0,
+        g: 0,
+        r: 0,
+        a: 255,
+    };
+    const WHITE: BGRA8 = BGRA8 {
+        b: 255,
+        g: 255,
+        r: 255,
+        a: 255,
+    };
+
+    #[test]
+    fn convolve() {
+        let mut data = vec![BLACK; 9];
+        data[4] = WHITE;
+
+        let identity = [0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0];
+        let mut img = ImgRefMut::new(&mut data, 3, 3);
+        super::convolve(&mut img, &identity, 3);
+        assert_eq!(data[4], WHITE);
+        assert_eq!(data[0], BLACK);
+
+        let mut box_blur = [1.0; 9];
+        super::normalize(&mut box_blur);
+        let mut img = ImgRefMut::new(&mut data, 3, 3);
+        super::convolve(&mut img, &box_blur, 3);
+        assert!(data.iter().all(|&px| px
+            == BGRA8 {
+                b: 28,
+                g: 28,
+                r: 28,
+                a: 255
+            }));
+    }
+
+    #[test]
+    fn normalize() {
+        let mut kernel = [1.0, 2.0, 1.0];
+        super::normalize(&mut kernel);
+        assert_eq!(kernel, [0.25, 0.5, 0.25]);
+
+        let mut edges = [-1.0, 2.0, -1.0];
+        super::normalize(&mut edges);
+        assert_eq!(edges, [-1.0, 2.0, -1.0]);
+    }
+}
@@ -0,0 +1,123 @@
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::cli::{self, PixelFormat};
+
+/// The `<pixfmt>` part of i3lock's `--raw=<width>x<height>:<pixfmt>` for `format`.
+/// i3lock ignores alpha, so it is passed as padding.
+pub const fn raw_format(format: PixelFormat) -> &'static str {
+    match format {
+        PixelFormat::Bgra8 => "native",
+        PixelFormat::Rgba8 => "rgbx",
+        PixelFormat::Rgb8 => "rgb",
+        PixelFormat::Bgr8 => "bgr",
+    }
+}
+
+/// Whether the installed i3lock knows `option`, which must be one that takes a value.
+///
+/// i3lock is run with just `option` and no value, so getopt bails out before
+/// anything is locked. Missing values and unknown options fail with different messages.
+pub fn supports_option(option: &str) -> bool {
+    probe(&[option], option)
+}
+
+/// Whether the installed i3lock knows the long `flag`, which must not take a value.
+///
+/// `flag` is followed by `--raw` without its value, so getopt bails out before
+/// anything is locked whether or not the flag is known.
+pub fn supports_flag(flag: &str) -> bool {
+    probe(&[flag, "--raw"], flag)
+}
+
+/// Run i3lock with `args` and check that it didn't reject `option`.
+fn probe(args: &[&str], option: &str) -> bool {
+    Command::new("i3lock")
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .map(|out| !rejected(&String::from_utf8_lossy(&out.stderr), option))
+        .unwrap_or(false)
+}
+
+/// Whether i3lock's `stderr` says that it doesn't know `option`.
+fn rejected(stderr: &str, option: &str) -> bool {
+    stderr.lines().any(|line| {
+        (line.contains("unrecognized option") || line.contains("invalid option"))
+            && line.contains(option)
+    })
+}
+
+/// The installed i3lock's `(major, minor)` version, from `i3lock --version`.
+pub fn version() -> io::Result<Option<(u32, u32)>> {
+    let out = Command::new("i3lock")
+        .arg("--version")
+        .stdin(Stdio::null())
+        .output()?;
+    // older versions print it to stderr
+    Ok(parse_version(&String::from_utf8_lossy(&out.stdout))
+        .or_else(|| parse_version(&String::from_utf8_lossy(&out.stderr))))
+}
+
+/// The version in `i3lock: version 2.13.c.4 © 2010 Michael Stapelberg` and the like.
+fn parse_version(text: &str) -> Option<(u32, u32)> {
+    let mut words = text.split_whitespace();
+    words.find(|w| *w == "version")?;
+    cli::parse_version(words.next()?).ok()
+}
+
+/// Read i3lock arguments from `path`, one per line, skipping blank lines and `#` comments.
+pub fn read_args_file(path: &Path) -> io::Result<Vec<OsString>> {
+    Ok(parse_args(&fs::read_to_string(path)?))
+}
+
+fn parse_args(text: &str) -> Vec<OsString> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(OsString::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version() {
+        assert_eq!(
+            parse_version("i3lock: version 2.13 © 2010 Michael Stapelberg"),
+            Some((2, 13))
+        );
+        assert_eq!(
+            parse_version(
+                "i3lock: version 2.13.c.4 © 2010 Michael Stapelberg, © 2015 Cassandra Fox"
+            ),
+            Some((2, 13))
+        );
+        assert_eq!(parse_version("i3lock: version 2.9-non-git"), Some((2, 9)));
+        assert_eq!(parse_version("command not found"), None);
+    }
+
+    #[test]
+    fn rejected() {
+        let stderr = "i3lock: unrecognized option '--debug'\ni3lock: Syntax: i3lock [-v] [-n]";
+        assert!(super::rejected(stderr, "--debug"));
+        let stderr = "i3lock: option '--raw' requires an argument\ni3lock: Syntax: i3lock [-v]";
+        assert!(!super::rejected(stderr, "--debug"));
+    }
+
+    #[test]
+    fn args_file() {
+        let args = parse_args(
+            "# colors\n--insidecolor=542095ff\n\n  --ringcolor=ffffffff  \n#--nofork\n-e\n",
+        );
+        assert_eq!(
+            args,
+            ["--insidecolor=542095ff", "--ringcolor=ffffffff", "-e"]
+        );
+    }
+}
@@ -1,3 +1,21 @@
+use std::sync::atomic::AtomicU64;
+
+/// Set from `--verbose-stages`, stages faster than this many milliseconds aren't timed.
+pub static STAGE_THRESHOLD: AtomicU64 = AtomicU64::new(0);
+
+/// The threshold stage timings are printed above by default.
+pub fn stage_threshold() -> std::time::Duration {
+    std::time::Duration::from_millis(STAGE_THRESHOLD.load(std::sync::atomic::Ordering::Relaxed))
+}
+
+/// Whether output may be colored, it isn't with `--no-color` or a non-empty `$NO_COLOR`.
+pub fn color_enabled(no_color: bool) -> bool {
+    !no_color
+        && std::env::var_os("NO_COLOR")
+            .filter(|v| !v.is_empty())
+            .is_none()
+}
+
 #[macro_export]
 macro_rules! timer_start {
     ($timer:ident) => {
@@ -8,25 +26,51 @@ macro_rules! timer_start {
 #[macro_export]
 macro_rules! timer_time {
     ($s:expr, $timer:ident) => {
-        debug!("{} took {:#?}", $s, $timer.elapsed());
+        timer_time!($s, $timer, $crate::macros::stage_threshold());
+    };
+
+    ($s:expr, $timer:ident, $threshold:expr) => {
+        let elapsed = $timer.elapsed();
+        if elapsed >= $threshold {
+            ::log::debug!("{} took {:#?}", $s, elapsed);
+        }
     };
 }
 
 #[macro_export]
 macro_rules! warn_disabled {
     ($s:expr) => {
-        eprintln!(
-            "{}",
-            Format::Warning(format!(
-                "Feature \"{f}\" was not enabled at compile-time. Skipping {f}.",
-                f = $s
-            ))
+        ::log::warn!(
+            "Feature \"{f}\" was not enabled at compile-time. Skipping {f}.",
+            f = $s
         );
     };
 }
 
 #[macro_export]
 macro_rules! time_routine {
+    ($operand:ident, $F:ident, $Arg:expr, $feat:literal, with $extra:expr) => {
+        if let Some(arg) = $Arg {
+        #[cfg(feature = $feat)]
+        {
+                let timer = Instant::now();
+
+                $operand.$F(arg, $extra);
+
+                if timer.elapsed() >= $crate::macros::stage_threshold() {
+                    ::log::debug!("`{}.{}({}, {})` took {:#?}", stringify!($operand), stringify!($F), arg, $extra, timer.elapsed());
+                }
+            }
+
+        #[cfg(not(feature = $feat))]
+        {
+            ::log::warn!(
+                "Feature {} was not enabled at compile-time. Skipping {}.", stringify!($feat), stringify!($F)
+            );
+        }
+        }
+    };
+
     ($operand:ident, $F:ident, $Arg:expr, $feat:literal) => {
         if let Some(arg) = $Arg {
         #[cfg(feature = $feat)]
@@ -35,16 +79,15 @@ macro_rules! time_routine {
 
                 $operand.$F(arg);
 
-                debug!("`{}.{}({})` took {:#?}", stringify!($operand), stringify!($F), arg, timer.elapsed());
+                if timer.elapsed() >= $crate::macros::stage_threshold() {
+                    ::log::debug!("`{}.{}({})` took {:#?}", stringify!($operand), stringify!($F), arg, timer.elapsed());
+                }
             }
 
         #[cfg(not(feature = $feat))]
         {
-            eprintln!(
-                "{}",
-                Format::Warning(format!(
-                        "Feature {} was not enabled at compile-time. Skipping {}.", stringify!($feat), stringify!($F)
-                ))
+            ::log::warn!(
+                "Feature {} was not enabled at compile-time. Skipping {}.", stringify!($feat), stringify!($F)
             );
         }
         }
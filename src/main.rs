@@ -1,36 +1,54 @@
 use std::borrow::Cow;
+use std::env;
 use std::error::Error;
+use std::ffi::OsString;
+use std::fs::File;
 use std::hint::unreachable_unchecked;
-use std::io::ErrorKind::WouldBlock;
 use std::io::{self, Write};
-use std::process::{Command, ExitStatus, Stdio};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::process::{self, Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::Ordering;
 use std::time::{Duration, Instant};
 
 use std::os::unix::process::ExitStatusExt;
-use std::thread::sleep;
 
-use imgref::ImgRefMut;
+use env_logger::WriteStyle;
 
+use imgref::{ImgRef, ImgRefMut, ImgVec};
+
+use log::{debug, info, warn, LevelFilter};
+
+use rgb::alt::BGRA8;
 use rgb::{ComponentBytes, FromSlice};
 
-use scrap::{Capturer, Display, Frame};
+#[cfg(feature = "self-test")]
+use sha2::{Digest, Sha256};
 
-use structopt::clap::Format;
+use structopt::clap::AppSettings;
 use structopt::StructOpt;
 
 use xcb::Connection;
 
 mod cli;
+#[macro_use]
 mod macros;
+mod algorithms;
+mod capture;
+mod filters;
+mod i3lock;
+mod monitor;
+mod output;
+mod testpattern;
+mod types;
+mod xrandr;
 
-use cli::Cli;
+use cli::{Cli, FlipMode, LogLevel, OutputFormat, PipelineStep, PixelFormat};
+use monitor::Monitor;
+use types::ClipRect;
 
 #[cfg(any(feature = "png", feature = "jpeg"))]
 use imagefmt::ColFmt;
-#[cfg(any(feature = "png", feature = "jpeg"))]
-use xcb::randr;
-#[cfg(any(feature = "png", feature = "jpeg"))]
-use xcb::Xid;
 
 #[cfg(feature = "scale")]
 mod scale;
@@ -47,15 +65,115 @@ mod brightness;
 #[cfg(feature = "brightness")]
 use brightness::BrightnessAdj;
 
+#[cfg(feature = "color-grading")]
+mod color;
+
 #[cfg(any(feature = "png", feature = "jpeg"))]
 mod overlay;
 #[cfg(any(feature = "png", feature = "jpeg"))]
 use overlay::Compose;
+#[cfg(any(feature = "png", feature = "jpeg"))]
+mod transform;
+
+#[cfg(feature = "config")]
+mod config;
+
+#[cfg(feature = "set-wallpaper")]
+mod wallpaper;
+
+#[cfg(feature = "notify")]
+mod notify;
+
+#[cfg(feature = "text")]
+mod text;
+
+#[cfg(feature = "clipboard")]
+mod clipboard;
+#[cfg(feature = "remote-icon")]
+mod remote;
+mod util;
+
+/// The order effects are applied in without `--pipeline`.
+const DEFAULT_PIPELINE: &[PipelineStep] = &[
+    PipelineStep::ScaleDown,
+    PipelineStep::Blur,
+    PipelineStep::ScaleUp,
+    PipelineStep::Pixelate,
+    PipelineStep::AutoContrast,
+    PipelineStep::Brighten,
+    PipelineStep::Darken,
+    PipelineStep::ColorMatrix,
+    PipelineStep::ColorBalance,
+    PipelineStep::Desaturate,
+    PipelineStep::Dye,
+    PipelineStep::Kernel,
+    PipelineStep::Quantize,
+    PipelineStep::Mirror,
+    PipelineStep::Shadow,
+    PipelineStep::Overlay,
+];
+
+/// `DEFAULT_PIPELINE` for `--icon-blend-behind`, the icon goes under the effects.
+const BEHIND_PIPELINE: &[PipelineStep] = &[
+    PipelineStep::Mirror,
+    PipelineStep::Overlay,
+    PipelineStep::ScaleDown,
+    PipelineStep::Blur,
+    PipelineStep::ScaleUp,
+    PipelineStep::Pixelate,
+    PipelineStep::AutoContrast,
+    PipelineStep::Brighten,
+    PipelineStep::Darken,
+    PipelineStep::ColorMatrix,
+    PipelineStep::ColorBalance,
+    PipelineStep::Desaturate,
+    PipelineStep::Dye,
+    PipelineStep::Kernel,
+    PipelineStep::Quantize,
+    PipelineStep::Shadow,
+];
+
+/// Effects run by `--self-test`, they're all integer math so the result is the same everywhere.
+#[cfg(feature = "self-test")]
+const SELF_TEST_ARGS: &[&str] = &[
+    "i3lockr",
+    "--pixelate=4",
+    "--brighten=10",
+    "--darken=40",
+    "--shadow-overlay",
+];
+
+/// SHA-256 of `testpattern::gradient(64, 64)` after `SELF_TEST_ARGS`.
+#[cfg(feature = "self-test")]
+const SELF_TEST_HASH: &str = "20899d509a7d57108db2ac92da2b1c2f41e9625c1a291bb7c81ea6950b11cf28";
+
+/// Largest blur radius allowed without `--max-blur-radius-override`,
+/// anything bigger takes ages on large screens.
+const MAX_BLUR_RADIUS: usize = 50;
+
+/// Exit code for `--fail-on-no-display`.
+const NO_DISPLAY_EXIT_CODE: i32 = 2;
+
+/// Border colors for `--debug-monitors`, indexed by monitor number.
+#[rustfmt::skip]
+const DEBUG_PALETTE: [BGRA8; 6] = [
+    BGRA8 { b: 0, g: 0, r: 255, a: 255 },
+    BGRA8 { b: 0, g: 255, r: 0, a: 255 },
+    BGRA8 { b: 255, g: 0, r: 0, a: 255 },
+    BGRA8 { b: 0, g: 255, r: 255, a: 255 },
+    BGRA8 { b: 255, g: 0, r: 255, a: 255 },
+    BGRA8 { b: 255, g: 255, r: 0, a: 255 },
+];
 
 fn main() -> Result<(), Box<dyn Error>> {
     timer_start!(everything);
     // parse args, handle custom `--version`
-    let args = Cli::from_args();
+    // clap colors its errors before --no-color has been parsed
+    let mut app = Cli::clap();
+    if !macros::color_enabled(env::args_os().any(|a| a == "--no-color")) {
+        app = app.setting(AppSettings::ColorNever);
+    }
+    let args = Cli::from_clap(&app.get_matches());
     if args.version {
         eprintln!(
             "{} v{} compiled for '{}' at {} ({}@{})",
@@ -69,232 +187,1586 @@ fn main() -> Result<(), Box<dyn Error>> {
         return Ok(());
     }
 
-    // init debug macro
-    macro_rules! debug {
-        ($($arg:tt)*) => {
-            if cfg!(debug_assertions) || args.verbose {
-                eprintln!("{f}:{l}:{c} {fmt}", f=file!(), l=line!(), c=column!(), fmt=format!($($arg)*));
-            }
-        }
+    // before loading the config file, so its warnings aren't lost
+    init_logger(&args);
+
+    #[cfg(feature = "config")]
+    if args.export_config {
+        print!("{}", config::export(args)?);
+        return Ok(());
+    }
+    #[cfg(feature = "config")]
+    let args = config::apply(args)?;
+    #[cfg(feature = "config")]
+    if let Some(level) = log_level(&args) {
+        log::set_max_level(level);
+    }
+
+    #[cfg(not(feature = "debug-stages"))]
+    if args.debug_save_stages.is_some() {
+        warn_disabled!("debug-stages");
+    }
+    if let Some(ms) = args.verbose_stages {
+        macros::STAGE_THRESHOLD.store(ms, Ordering::Relaxed);
+    }
+
+    #[cfg(not(feature = "config"))]
+    if args.export_config {
+        warn_disabled!("config");
+        return Ok(());
+    }
+    #[cfg(not(feature = "config"))]
+    if args.config.is_some() || args.profile.is_some() {
+        warn_disabled!("config");
+    }
+
+    let mut args = args;
+    if let Some(ref path) = args.i3lock_args_file {
+        let extra =
+            i3lock::read_args_file(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        args.i3lock.extend(extra);
+    }
+    if args.strip_alpha {
+        args.pixel_format = PixelFormat::Rgb8;
     }
 
     debug!("Found args: {:#?}", args);
 
-    let (conn, screen_num) = Connection::connect(None)?;
+    // check these before doing any work
+    if let Err(problems) = validate_args(&args) {
+        for problem in &problems {
+            log::error!("{}", problem);
+        }
+        return Err(format!("Found {} problem(s) with the arguments", problems.len()).into());
+    }
+    if args.format_check {
+        println!("OK");
+        return Ok(());
+    }
+    let grace = match args.delay_lock.filter(|_| calls_i3lock(&args)) {
+        Some(ms) if i3lock::supports_option("--grace") => {
+            Some(format!("--grace={}", (ms.get() + 999) / 1000))
+        }
+        Some(_) => return Err("--delay-lock needs an i3lock that supports --grace".into()),
+        None => None,
+    };
+    if let (Some(seconds), true) = (args.pam_auth_timeout, calls_i3lock(&args)) {
+        if !i3lock::supports_option("--auth-timeout") {
+            return Err("--pam-auth-timeout needs an i3lock that supports --auth-timeout".into());
+        }
+        args.i3lock
+            .push(format!("--auth-timeout={}", seconds).into());
+    }
+    if args.verbose_i3lock && calls_i3lock(&args) {
+        if i3lock::supports_flag("--debug") {
+            args.i3lock.push("--debug".into());
+        } else {
+            warn!("i3lock doesn't support --debug, ignoring --verbose-i3lock");
+        }
+    }
+    if !args.skip_i3lock_check && calls_i3lock(&args) && !i3lock::supports_option("--raw") {
+        return Err("i3lock doesn't support --raw, or isn't installed. \
+                    Please upgrade i3lock, or pass --skip-i3lock-check if you're sure it works."
+            .into());
+    }
+    if let (Some((major, minor)), true) = (args.version_pin, calls_i3lock(&args)) {
+        match i3lock::version()? {
+            Some(found) if found >= (major, minor) => {
+                debug!("Found i3lock {}.{}", found.0, found.1)
+            }
+            Some(found) => {
+                return Err(format!(
+                    "i3lock {}.{} is older than --version-pin {}.{}",
+                    found.0, found.1, major, minor
+                )
+                .into())
+            }
+            None => return Err("Couldn't tell which version i3lock is for --version-pin".into()),
+        }
+    }
 
-    // setup scrap
-    timer_start!(scrap);
+    let gzip = args.compress_output && args.i3lock_supports_gzip;
+    if args.compress_output && !args.i3lock_supports_gzip {
+        warn!("--compress-output does nothing without --i3lock-supports-gzip");
+    }
+    #[cfg(not(feature = "gzip"))]
+    if gzip {
+        warn_disabled!("gzip");
+    }
+    #[cfg(not(feature = "scrot"))]
+    if args.scrot_compat {
+        warn_disabled!("scrot");
+    }
 
-    let mut max_height: usize = 0;
-    let mut max_width:  usize = 0;
-    for disp in Display::all()? {
-        if disp.bottom() as usize > max_height {
-            max_height = disp.bottom() as usize;
+    // raw input never touches the display
+    if args.fail_on_no_display && args.raw_input.is_none() {
+        if let Err(e) = capture::primary_size() {
+            log::error!("No display found ({}), is $DISPLAY set?", e);
+            process::exit(NO_DISPLAY_EXIT_CODE);
         }
-        if disp.right() as usize > max_width {
-            max_width = disp.right() as usize;
+    }
+
+    #[cfg(not(feature = "self-test"))]
+    if args.self_test {
+        warn_disabled!("self-test");
+        return Err("Nothing was tested".into());
+    }
+    #[cfg(feature = "self-test")]
+    if args.self_test {
+        let actual = self_test();
+        if actual == SELF_TEST_HASH {
+            println!("PASS");
+            return Ok(());
         }
+        println!("FAIL");
+        println!("expected: {}", SELF_TEST_HASH);
+        println!("actual:   {}", actual);
+        return Err("Self-test failed, the effects don't produce the expected image".into());
     }
 
-    let mut multimon_buffer = vec![rgb::alt::BGRA::<u8>::default(); max_width * max_height];
-    
-    for (i, disp) in Display::all()?.into_iter().enumerate() {
-        let x_offset = disp.left() as usize;
-        let y_offset = disp.top() as usize;
-        let mut capture = Capturer::new(disp)?;
+    if args.benchmark_effects {
+        let (w, h) = match (args.width, args.height) {
+            (Some(w), Some(h)) => (w.get(), h.get()),
+            (w, h) => {
+                let (primary_w, primary_h) = capture::primary_size()?;
+                (
+                    w.map_or(primary_w, NonZeroUsize::get),
+                    h.map_or(primary_h, NonZeroUsize::get),
+                )
+            }
+        };
+        debug!("Benchmarking effects on a {}x{} image", w, h);
 
-        let (w, h) = (capture.width(), capture.height());
-        timer_time!("Setting up scrap", scrap);
+        let mut frame = ImgVec::new(vec![BGRA8::default(); w * h], w, h);
+        apply_effects(&mut frame.as_mut(), &args, &[Monitor::covering(w, h)], None);
+        timer_time!("Everything", everything);
+        return Ok(());
+    }
 
-        // take the screenshot
-        timer_start!(screenshot);
-        let mut buffer: Frame;
-        loop {
-            match capture.frame() {
-                Ok(buf) => {
-                    buffer = buf;
-                    break;
+    // held until we return
+    let _lock = if args.xdg_runtime_lock {
+        match env::var_os("XDG_RUNTIME_DIR") {
+            Some(dir) => match util::LockFile::acquire(PathBuf::from(dir).join("i3lockr.lock"))? {
+                Some(lock) => Some(lock),
+                None => {
+                    debug!("Another i3lockr is already running");
+                    return Ok(());
                 }
-                Err(e) => {
-                    if e.kind() == WouldBlock {
-                        sleep(Duration::from_millis(33));
-                        continue;
+            },
+            None => {
+                warn!("$XDG_RUNTIME_DIR isn't set, ignoring --xdg-runtime-lock");
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let monitors = match (args.raw_width, args.raw_height) {
+        // there's no display to ask, so treat the raw image as one big monitor
+        (Some(w), Some(h)) => vec![Monitor::covering(w.get(), h.get())],
+        _ => {
+            let (conn, screen_num) = Connection::connect(None)?;
+            if args.xinerama_mode {
+                monitor::query_xinerama(&conn, args.monitor_order)?
+            } else {
+                match monitor::query_retry(
+                    &conn,
+                    screen_num,
+                    args.monitor_order,
+                    args.detect_displays_retry,
+                ) {
+                    Ok(monitors) => monitors,
+                    Err(e) if args.xrandr_fallback => {
+                        debug!("Querying RandR failed ({}), asking xrandr instead", e);
+                        xrandr::query(args.monitor_order)?
                     }
+                    Err(e) => return Err(e.into()),
                 }
             }
         }
-        timer_time!(format!("Capturing screenshot on display {}", i), screenshot);
-
-        // convert to imgref
-        let buf_bgra = buffer.as_bgra_mut();
-        for y in 0..h {
-            let src_start = w * y;
-            let src_end   = src_start + w;
-            let dst_start = (y + y_offset) * max_width + x_offset;
-            let dst_end   = dst_start + w;
-            
-            multimon_buffer[dst_start..dst_end].copy_from_slice(&buf_bgra[src_start..src_end]);
-        }
-    }
-
-    timer_start!(convert);
-    let mut screenshot = ImgRefMut::new(&mut multimon_buffer, max_width, max_height);
-    timer_time!("Converting image", convert);
-
-    // scaling is unsafe
-    unsafe {
-        time_routine!(
-            screenshot,
-            scale_down,
-            args.factor,
-            "scale",
-            blur,
-            args.radius,
-            "blur",
-            scale_up,
-            args.factor,
-            "scale",
-            brighten,
-            args.bright,
-            "brightness",
-            darken,
-            args.dark,
-            "brightness"
+    };
+
+    // not fatal, the layout may just have changed since the option was written down
+    for i in args
+        .ignore
+        .iter()
+        .filter(|&&i| !monitors.iter().any(|m| m.index == i))
+    {
+        warn!(
+            "--ignore-monitors {}: there is no monitor {}, only {} were detected",
+            i,
+            i,
+            monitors.len()
         );
     }
+    // from here on only --ignore-monitors has to be checked
+    for name in &args.ignore_named {
+        match monitors.iter().find(|m| m.name == *name) {
+            Some(m) => args.ignore.push(m.index),
+            None => warn!("--ignore-monitors-named: no monitor named \"{}\"", name),
+        }
+    }
 
-    // overlay/invert on each monitor
-    if let Some(ref path) = args.path {
-        #[cfg(any(feature = "png", feature = "jpeg"))]
-        {
-            timer_start!(decode);
-            let image = imagefmt::read(path, ColFmt::BGRA)?;
-            let image = imgref::ImgRef::new(image.buf.as_bgra(), image.w, image.h);
-            timer_time!("Decoding overlay image", decode);
-
-            // get handle on monitors
-            let screen = conn
-                .get_setup()
-                .roots()
-                .nth(screen_num as usize)
-                .unwrap_or_else(|| unreachable!());
-
-            let cookie = conn.send_request(&randr::GetScreenResources {
-                window: screen.root(),
-            });
-            let reply = conn.wait_for_reply(cookie)?;
-
-            for (w, h, x, y) in reply
-                .crtcs()
-                .iter()
-                .filter_map(|crtc| {
-                    let cookie = conn.send_request(&randr::GetCrtcInfo {
-                        crtc: *crtc,
-                        config_timestamp: reply.timestamp(),
-                    });
-                    conn.wait_for_reply(cookie).ok()
-                })
-                .enumerate()
-                .filter(|(i, m)| !m.mode().is_none() && !args.ignore.contains(i))
-                .map(|(_, m)| {
-                    (
-                        usize::from(m.width()),
-                        usize::from(m.height()),
-                        m.x() as usize,
-                        m.y() as usize,
-                    )
-                })
+    // mirrored monitors get the icon twice
+    for (i, a) in monitors.iter().enumerate() {
+        for b in &monitors[i + 1..] {
+            let overlap = a
+                .rect()
+                .overlap_percentage(&b.rect())
+                .max(b.rect().overlap_percentage(&a.rect()));
+            if overlap > 50.0 && !args.ignore.contains(&a.index) && !args.ignore.contains(&b.index)
             {
-                let (x_off, y_off) = if args.pos.is_empty() {
-                    if image.width() > w || image.height() > h {
-                        eprintln!(
-                            "{}",
-                            Format::Warning(
-                                "Your image is larger than your monitor, image positions may be off!"
-                                )
-                            );
-                    }
-                    (
-                        w / 2 - image.width() / 2 + x,
-                        h / 2 - image.height() / 2 + y,
-                    )
-                } else {
-                    unsafe {
-                        (
-                            wrap_to_screen(*args.pos.get_unchecked(0), w + x),
-                            wrap_to_screen(*args.pos.get_unchecked(1), h + y),
-                        )
-                    }
-                };
-
-                debug!(
-                    "Calculated image position on monitor: ({},{})",
-                    x_off, y_off
+                warn!(
+                    "Monitors {} and {} overlap by {:.0}%, if they're mirrored \
+                     try --ignore-monitors {} so the icon isn't drawn twice",
+                    a.index, b.index, overlap, b.index
                 );
-
-                timer_start!(overlay);
-                if args.invert {
-                    screenshot.invert(Some(image), x_off, y_off);
-                } else {
-                    screenshot.compose(image, x_off, y_off);
-                }
-                timer_time!("Overlaying image", overlay);
             }
         }
-        #[cfg(not(any(feature = "png", feature = "jpeg")))]
-        warn_disabled!("png/jpeg overlay");
-    } else if args.invert {
+    }
+
+    let lock_rect = match args.lockscreen_only_on {
+        Some(ref name) => Some(
+            monitors
+                .iter()
+                .find(|m| m.name == *name)
+                .ok_or_else(|| format!("--lockscreen-only-on: no monitor named \"{}\"", name))?
+                .rect(),
+        ),
+        None => None,
+    };
+
+    if args.monitor_info_json {
+        #[cfg(feature = "json")]
+        println!("{}", serde_json::to_string(&monitors)?);
+        #[cfg(not(feature = "json"))]
+        warn_disabled!("json");
+        return Ok(());
+    }
+
+    if let Some(limit) = args.max_memory {
+        let (w, h) = match args.raw_input {
+            Some(_) => (monitors[0].width, monitors[0].height),
+            None => capture::screen_size()?,
+        };
+        let estimate = estimate_memory(&args, &monitors, w, h);
+        debug!(
+            "Estimated peak memory use: {} MiB",
+            estimate / (1024 * 1024)
+        );
+        if estimate > limit.get() * 1024 * 1024 {
+            return Err(format!(
+                "Locking a {}x{} screen would take about {} MiB, more than --max-memory {}. \
+                 Try --ignore-monitors or fewer effects.",
+                w,
+                h,
+                estimate.div_ceil(1024 * 1024),
+                limit
+            )
+            .into());
+        }
+    }
+
+    let icon = icon_path(&args);
+
+    // decoded up front so that --pipeline can overlay it at any point
+    let icon = match icon {
+        _ if args.no_effects => None,
+        #[cfg(feature = "clipboard")]
+        _ if args.icon_from_clipboard => Some(load_icon(clipboard::read_image()?, &args)?),
+        #[cfg(not(feature = "clipboard"))]
+        _ if args.icon_from_clipboard => {
+            warn_disabled!("clipboard");
+            None
+        }
         #[cfg(any(feature = "png", feature = "jpeg"))]
+        Some(ref path) => decode_icon(path, &args)?
+            .map(|image| load_icon(image, &args))
+            .transpose()?,
+        #[cfg(not(any(feature = "png", feature = "jpeg")))]
+        Some(_) => {
+            warn_disabled!("png/jpeg overlay");
+            None
+        }
+        None => None,
+    };
+    let icon = icon.as_ref().map(ImgVec::as_ref);
+    if icon.is_some()
+        && !args.pipeline.is_empty()
+        && !args.pipeline.contains(&PipelineStep::Overlay)
+    {
+        warn!("--pipeline has no overlay step, the icon won't be drawn");
+    }
+
+    if let (true, Some(image)) = (args.center_on_cursor, icon) {
+        match capture::cursor_position() {
+            Ok((x, y)) => {
+                debug!("Centering the icon on the cursor at ({},{})", x, y);
+                args.pos = vec![
+                    x.saturating_sub(image.width() / 2) as f32,
+                    y.saturating_sub(image.height() / 2) as f32,
+                ];
+            }
+            Err(e) => warn!(
+                "Couldn't find the cursor, ignoring --center-on-cursor: {}",
+                e
+            ),
+        }
+    }
+
+    if let Some(seconds) = args.lock_after {
+        countdown(seconds.get());
+    }
+    if let Some(ms) = args.notify_before_lock {
+        notify_before_lock(Duration::from_millis(ms.get()));
+    }
+
+    #[cfg(feature = "text")]
+    let watermark = match args.watermark {
+        Some(ref text) => Some(watermark_stamp(&args, text)?),
+        None => None,
+    };
+    #[cfg(not(feature = "text"))]
+    let watermark: Option<ImgVec<BGRA8>> = {
+        if args.watermark.is_some() {
+            warn_disabled!("text");
+        }
+        None
+    };
+    let watermark = watermark.as_ref().map(ImgVec::as_ref);
+
+    if let (Some(image), false) = (icon, args.force_large_icon) {
+        for m in monitors.iter().filter(|m| !args.ignore.contains(&m.index)) {
+            algorithms::check_icon_fits(image.width(), image.height(), m.width, m.height).map_err(
+                |e| {
+                    format!(
+                        "Monitor {}: {}, pass --force-large-icon to use it anyway",
+                        m.index, e
+                    )
+                },
+            )?;
+        }
+    }
+
+    let mut frame = capture_frame(&args, &monitors)?;
+    render(&mut frame, &args, &monitors, icon, watermark);
+
+    if args.preview {
+        preview(&frame)?;
+    }
+
+    if args.apply_to_root {
+        #[cfg(feature = "set-wallpaper")]
         {
-            timer_start!(invert);
-            screenshot.invert(None, 0, 0);
-            timer_time!("Inverting image", invert);
+            timer_start!(wallpaper);
+            wallpaper::set(frame.as_ref())?;
+            timer_time!("Setting the wallpaper", wallpaper);
         }
-        #[cfg(not(any(feature = "png", feature = "jpeg")))]
-        warn_disabled!("invert");
+        #[cfg(not(feature = "set-wallpaper"))]
+        warn_disabled!("set-wallpaper");
+        timer_time!("Everything", everything);
+        return Ok(());
     }
 
-    //TODO draw text
+    if let Some(ref path) = args.output_png {
+        let path = output_path(path, args.scrot_compat);
+        #[cfg(feature = "png")]
+        output::write_png(&path, frame.as_ref())
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        #[cfg(not(feature = "png"))]
+        warn_disabled!("png");
+        timer_time!("Everything", everything);
+        return Ok(());
+    }
+
+    if let Some(ref path) = args.output_bmp {
+        let path = output_path(path, args.scrot_compat);
+        output::save_bmp(&path, frame.as_ref())
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        timer_time!("Everything", everything);
+        return Ok(());
+    }
+
+    if args.stdout {
+        let stdout = io::stdout();
+        let mut out = io::BufWriter::new(stdout.lock());
+        if args.format_raw_header {
+            if args.stdout_format == OutputFormat::Raw {
+                write_raw_header(&mut out, frame.width(), frame.height(), RAW_FORMAT_BGRA8)?;
+            } else {
+                warn!("--format-raw-header only applies to raw output, ignoring it");
+            }
+        }
+        output::write(&mut out, frame.as_ref(), args.stdout_format)?;
+        out.flush()?;
+        timer_time!("Everything", everything);
+        return Ok(());
+    }
 
     // check if we're forking
     timer_start!(fork);
     let nofork = forking(args.i3lock.iter().map(|x| x.as_os_str().to_string_lossy()));
     timer_time!("Checking for nofork", fork);
 
-    // call i3lock
-    debug!("Calling i3lock with args: {:?}", args.i3lock);
+    if !nofork {
+        if args.after_lock.is_some() {
+            warn!("--after-lock has no effect unless i3lock is called with --nofork");
+        }
+        if args.retry_on_wrong_password > 0 {
+            warn!("--retry-on-wrong-password has no effect unless i3lock is called with --nofork");
+        }
+
+        let mut cmd = spawn_i3lock(
+            lock_view(&frame, lock_rect),
+            args.pixel_format,
+            gzip,
+            grace.as_deref(),
+            &args.i3lock,
+        )?;
+        timer_time!("Everything", everything);
+        return match cmd.try_wait() {
+            Ok(None) => Ok(()),
+            Ok(Some(status)) => status_to_result(status),
+            Err(e) => Err(e.into()),
+        };
+    }
+
+    let mut retries = args.retry_on_wrong_password;
+    loop {
+        let mut cmd = spawn_i3lock(
+            lock_view(&frame, lock_rect),
+            args.pixel_format,
+            gzip,
+            grace.as_deref(),
+            &args.i3lock,
+        )?;
+        timer_time!("Everything", everything);
+
+        debug!("Asked i3lock not to fork, calling wait()");
+        let status = cmd.wait()?;
+
+        // killed by a signal isn't a failed unlock
+        if !status.success() && status.code().is_some() && retries > 0 {
+            retries -= 1;
+            debug!(
+                "i3lock exited with {}, locking again with a fresh screenshot ({} retries left)",
+                status, retries
+            );
+            frame = capture_frame(&args, &monitors)?;
+            render(&mut frame, &args, &monitors, icon, watermark);
+            continue;
+        }
+        status_to_result(status)?;
+        break;
+    }
+
+    if let Some(after) = args.after_lock {
+        debug!("Running after-lock command: {}", after);
+        status_to_result(Command::new("sh").arg("-c").arg(after).status()?)?;
+    }
+    Ok(())
+}
+
+/// `--log-level`, or the level `--verbose` or `--quiet` stand for.
+fn log_level(args: &Cli) -> Option<LevelFilter> {
+    let level = match args.log_level {
+        Some(level) => level,
+        None if args.quiet => LogLevel::Error,
+        None if args.verbose || args.benchmark_effects || args.verbose_stages.is_some() => {
+            LogLevel::Debug
+        }
+        None if args.debug_monitors => LogLevel::Info,
+        None => return None,
+    };
+    Some(match level {
+        LogLevel::Error => LevelFilter::Error,
+        LogLevel::Warn => LevelFilter::Warn,
+        LogLevel::Info => LevelFilter::Info,
+        LogLevel::Debug => LevelFilter::Debug,
+        LogLevel::Trace => LevelFilter::Trace,
+    })
+}
+
+/// Log at `log_level`. Without it `RUST_LOG` is respected, debug builds default to debug output.
+/// A level from the config file is set later with `log::set_max_level`, so only
+/// `RUST_LOG` keeps it from going above what it allows.
+fn init_logger(args: &Cli) {
+    let level = log_level(args);
+
+    let mut logger = env_logger::Builder::new();
+    logger.filter_level(LevelFilter::Trace);
+    if level.is_none() {
+        logger.parse_env("RUST_LOG");
+    }
+    if !macros::color_enabled(args.no_color) {
+        logger.write_style(WriteStyle::Never);
+    }
+    logger.format_timestamp(None).init();
+
+    let default = if cfg!(debug_assertions) {
+        LevelFilter::Debug
+    } else {
+        LevelFilter::Warn
+    };
+    match level {
+        Some(level) => log::set_max_level(level),
+        None if env::var_os("RUST_LOG").is_none() => log::set_max_level(default),
+        // the logger already set the most RUST_LOG lets through
+        None => {}
+    }
+}
+
+/// Wait `seconds`, counting down in a desktop notification with the `notify` feature.
+fn countdown(seconds: u64) {
+    debug!("Locking in {} seconds", seconds);
+    for left in (1..=seconds).rev() {
+        #[cfg(feature = "notify")]
+        if let Err(e) = notify::send(
+            &format!("Locking the screen in {} seconds", left),
+            Duration::from_millis(1500),
+        ) {
+            debug!("{}", e);
+        }
+        #[cfg(not(feature = "notify"))]
+        let _ = left;
+        std::thread::sleep(Duration::from_secs(1));
+    }
+}
+
+/// Warn with a desktop notification, then wait `delay` before locking.
+fn notify_before_lock(delay: Duration) {
+    #[cfg(feature = "notify")]
+    if let Err(e) = notify::send(
+        &format!(
+            "Screen locking in {} seconds",
+            (delay.as_millis() + 999) / 1000
+        ),
+        delay,
+    ) {
+        warn!("{}, locking anyway", e);
+    }
+    #[cfg(not(feature = "notify"))]
+    warn_disabled!("notify");
+    std::thread::sleep(delay);
+}
+
+/// `--icon`, or the icon set in the environment.
+fn icon_path(args: &Cli) -> Option<PathBuf> {
+    // lets an administrator set an icon for everyone
+    args.path.clone().or_else(|| {
+        ["I3LOCKR_ICON", "IMAGE_PATH"]
+            .iter()
+            .filter_map(env::var_os)
+            .find(|p| !p.is_empty())
+            .map(PathBuf::from)
+    })
+}
+
+/// Whether this run ends with handing the image to i3lock.
+const fn calls_i3lock(args: &Cli) -> bool {
+    !(args.self_test
+        || args.format_check
+        || args.benchmark_effects
+        || args.monitor_info_json
+        || args.apply_to_root
+        || args.output_png.is_some()
+        || args.output_bmp.is_some()
+        || args.stdout)
+}
+
+/// `path` with `--scrot-compat` placeholders expanded when `scrot` is set.
+#[cfg(feature = "scrot")]
+fn output_path(path: &Path, scrot: bool) -> Cow<'_, Path> {
+    if scrot {
+        Cow::Owned(output::expand_scrot_path(path, &chrono::Local::now()))
+    } else {
+        Cow::Borrowed(path)
+    }
+}
+
+#[cfg(not(feature = "scrot"))]
+const fn output_path(path: &Path, _scrot: bool) -> Cow<'_, Path> {
+    Cow::Borrowed(path)
+}
+
+/// Take the screenshot, or whatever replaces it.
+fn capture_frame(args: &Cli, monitors: &[Monitor]) -> Result<ImgVec<BGRA8>, Box<dyn Error>> {
+    #[cfg(any(feature = "png", feature = "jpeg"))]
+    if let Some(ref path) = args.background_image {
+        timer_start!(background);
+        let (w, h) = capture::screen_size()?;
+        let image = decode_file(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let image = ImgRef::new(image.buf.as_bgra(), image.w, image.h);
+        let frame = resize(image, w, h, args.scale_quality);
+        timer_time!("Loading background image", background);
+        return Ok(frame);
+    }
+
+    let frame = if let Some(ref path) = args.raw_input {
+        timer_start!(raw);
+        let (w, h) = (monitors[0].width, monitors[0].height);
+        let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let frame = capture::read_raw(io::BufReader::new(file), w, h)?;
+        timer_time!("Reading raw image", raw);
+        frame
+    } else if let Some(pattern) = args.checkerboard {
+        let (w, h) = capture::screen_size()?;
+        testpattern::checkerboard(w, h, pattern)
+    } else if args.read_image_stdin {
+        timer_start!(stdin);
+        let (screen_w, screen_h) = capture::screen_size()?;
+        let w = args.stdin_width.map_or(screen_w, NonZeroUsize::get);
+        let h = args.stdin_height.map_or(screen_h, NonZeroUsize::get);
+        // room for a HiDPI image, but not for a typo in --stdin-width
+        let max = 4 * screen_w * screen_h;
+        let frame =
+            capture::read_stdin_image(io::stdin().lock(), args.stdin_image_format, w, h, max)?;
+        if (frame.width(), frame.height()) != (screen_w, screen_h) {
+            warn!(
+                "The image from stdin is {}x{} but the screen is {}x{}, monitor positions may be off!",
+                frame.width(),
+                frame.height(),
+                screen_w,
+                screen_h
+            );
+        }
+        timer_time!("Reading image from stdin", stdin);
+        frame
+    } else {
+        capture::capturer(args.screenshot_method, args.screenshot_delay_frames).capture()?
+    };
+    Ok(frame)
+}
+
+/// Apply the effects and anything else that gets drawn onto `frame`.
+fn render(
+    frame: &mut ImgVec<BGRA8>,
+    args: &Cli,
+    monitors: &[Monitor],
+    icon: Option<ImgRef<BGRA8>>,
+    watermark: Option<ImgRef<BGRA8>>,
+) {
+    if args.no_effects {
+        debug!("--no-effects given, locking with the plain screenshot");
+        return;
+    }
+
+    let mut screenshot = frame.as_mut();
+
+    let rects: Vec<_> = monitors.iter().map(Monitor::rect).collect();
+    let saved = args
+        .title_bar_crop
+        .map(|rows| algorithms::save_top_rows(&screenshot, &rects, rows.get()));
+
+    if let Some(radius) = args.background_blur {
+        #[cfg(feature = "blur")]
+        {
+            timer_start!(background_blur);
+            let size = args.background_clear_size;
+            let clear: Vec<_> = rects.iter().map(|r| r.centered(size, size)).collect();
+            let centers = algorithms::save_rects(&screenshot, &clear);
+            screenshot.blur(radius);
+            algorithms::restore_rects(&mut screenshot, &clear, &centers);
+            timer_time!("Blurring the background", background_blur);
+        }
+        #[cfg(not(feature = "blur"))]
+        {
+            let _ = radius;
+            warn_disabled!("blur");
+        }
+    }
+
+    apply_effects(&mut screenshot, args, monitors, icon);
+
+    if let Some(ref saved) = saved {
+        algorithms::restore_top_rows(&mut screenshot, &rects, saved);
+    }
+
+    #[cfg(feature = "text")]
+    if let Some(mark) = watermark {
+        timer_start!(watermark);
+        for rect in &rects {
+            text::tile(&mut screenshot, mark, *rect);
+        }
+        timer_time!("Drawing watermark", watermark);
+    }
+    #[cfg(not(feature = "text"))]
+    let _ = watermark;
+
+    if let Some(grid) = args.grid_overlay {
+        let (r, g, b) = grid.color;
+        algorithms::draw_grid(
+            &mut screenshot,
+            grid.cols.get(),
+            grid.rows.get(),
+            BGRA8 { b, g, r, a: 255 },
+            grid.opacity,
+        );
+    }
+
+    if args.debug_monitors {
+        for m in monitors {
+            let color = DEBUG_PALETTE[m.index % DEBUG_PALETTE.len()];
+            info!(
+                "Monitor {}: {}x{}+{}+{}, border #{:02x}{:02x}{:02x}",
+                m.index, m.width, m.height, m.x, m.y, color.r, color.g, color.b
+            );
+            algorithms::draw_border(&mut screenshot, m.rect(), 3, color);
+        }
+    }
+
+    // the scaled down image is in the top left corner
+    let crop = args.scale_down_only && cfg!(feature = "scale");
+    if let Some(factor) = args.factor.filter(|_| crop) {
+        let (w, h) = (frame.width() / factor, frame.height() / factor);
+        let buf: Vec<_> = frame.sub_image(0, 0, w, h).pixels().collect();
+        *frame = ImgVec::new(buf, w, h);
+    }
+}
+
+/// The `--watermark` text, tilted, ready to be tiled over each monitor.
+#[cfg(feature = "text")]
+fn watermark_stamp(args: &Cli, text: &str) -> Result<ImgVec<BGRA8>, Box<dyn Error>> {
+    timer_start!(watermark);
+    let font = text::load_font(args.watermark_font.as_deref())?;
+    let (r, g, b) = args.watermark_color;
+    let a = (args.watermark_opacity * 255.0).round() as u8;
+    let line = text::render_line(&font, text, args.watermark_size, BGRA8 { b, g, r, a });
+    let stamp = transform::rotate(line.as_ref(), -30.0);
+    timer_time!("Rendering watermark", watermark);
+    Ok(stamp)
+}
+
+/// Show `frame` in feh and wait for its window to be closed.
+fn preview(frame: &ImgVec<BGRA8>) -> Result<(), Box<dyn Error>> {
+    debug!("Showing a preview, close the window to lock");
+    let mut cmd = Command::new("feh")
+        .args(["--auto-zoom", "--title", "i3lockr preview", "-"])
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Couldn't run feh for --preview: {}", e))?;
+
+    {
+        let mut stdin = io::BufWriter::new(cmd.stdin.take().expect("Failed to take cmd.stdin"));
+        output::write_ppm(&mut stdin, frame.as_ref())?;
+        // stdin is closed at the end of this block, so feh sees where the image ends
+        stdin.flush()?;
+    }
+
+    let status = cmd.wait()?;
+    if !status.success() {
+        return Err(format!("feh exited with {}", status).into());
+    }
+    Ok(())
+}
+
+/// Start i3lock and hand it `frame` as `format`.
+fn spawn_i3lock(
+    frame: ImgRef<BGRA8>,
+    format: PixelFormat,
+    gzip: bool,
+    grace: Option<&str>,
+    i3lock_args: &[OsString],
+) -> io::Result<Child> {
+    debug!("Calling i3lock with args: {:?}", i3lock_args);
     let mut cmd = Command::new("i3lock")
         .args(&[
             "-i",
             "/dev/stdin",
-            //FIXME
-            &format!("--raw={}x{}:native", max_width, max_height),
+            &format!(
+                "--raw={}x{}:{}",
+                frame.width(),
+                frame.height(),
+                i3lock::raw_format(format)
+            ),
         ])
-        .args(args.i3lock)
+        .args(grace)
+        .args(i3lock_args)
         .stdin(Stdio::piped())
         .spawn()?;
 
     // pass image bytes
-    cmd.stdin
-        .as_mut()
-        .expect("Failed to take cmd.stdin.as_mut()")
-        .write_all(screenshot.into_buf().as_bytes())?;
+    {
+        let stdin = cmd
+            .stdin
+            .as_mut()
+            .expect("Failed to take cmd.stdin.as_mut()");
+        let mut stdin = io::BufWriter::new(stdin);
+        #[cfg(feature = "gzip")]
+        if gzip {
+            output::write_gzip(&mut stdin, frame, format)?;
+        } else {
+            output::write_pixels(&mut stdin, frame, format)?;
+        }
+        #[cfg(not(feature = "gzip"))]
+        {
+            let _ = gzip;
+            output::write_pixels(&mut stdin, frame, format)?;
+        }
+        stdin.flush()?;
+    }
 
-    timer_time!("Everything", everything);
+    Ok(cmd)
+}
 
-    if nofork {
-        debug!("Asked i3lock not to fork, calling wait()");
-        match cmd.wait() {
-            Ok(status) => status_to_result(status),
-            Err(e) => Err(e.into()),
+/// The part of `frame` to lock, `rect` is the `--lockscreen-only-on` monitor.
+fn lock_view(frame: &ImgVec<BGRA8>, rect: Option<ClipRect>) -> ImgRef<BGRA8> {
+    let bounds = ClipRect::new(0, 0, frame.width(), frame.height());
+    match rect.and_then(|r| r.intersect(&bounds)) {
+        Some(r) => frame.sub_image(r.x, r.y, r.width, r.height),
+        None => frame.as_ref(),
+    }
+}
+
+/// Decode the image at `path`, memory-mapping it with the "mmap-icons" feature.
+#[cfg(any(feature = "png", feature = "jpeg"))]
+fn decode_file(path: &Path) -> Result<imagefmt::Image<u8>, Box<dyn Error>> {
+    #[cfg(feature = "mmap-icons")]
+    {
+        let file = File::open(path)?;
+        // SAFETY: truncating the file while it's mapped would SIGBUS us,
+        // nothing should be rewriting the icon while we lock
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        Ok(imagefmt::read_from(
+            &mut io::Cursor::new(&map[..]),
+            ColFmt::BGRA,
+        )?)
+    }
+
+    #[cfg(not(feature = "mmap-icons"))]
+    Ok(imagefmt::read(path, ColFmt::BGRA)?)
+}
+
+/// Run `SELF_TEST_ARGS` on the test image, returns the hex SHA-256 of the result.
+#[cfg(feature = "self-test")]
+fn self_test() -> String {
+    let args = Cli::from_iter(SELF_TEST_ARGS);
+    let mut frame = testpattern::gradient(64, 64);
+    apply_effects(
+        &mut frame.as_mut(),
+        &args,
+        &[Monitor::covering(64, 64)],
+        None,
+    );
+    format!("{:x}", Sha256::digest(frame.buf().as_bytes()))
+}
+
+/// Rough peak memory use in bytes for a `width`x`height` screen, the icon isn't counted.
+fn estimate_memory(args: &Cli, monitors: &[Monitor], width: usize, height: usize) -> usize {
+    const PIXEL: usize = std::mem::size_of::<BGRA8>();
+
+    let mut bytes = width * height * PIXEL;
+
+    // scrap captures one display at a time into its own buffer
+    if args.raw_input.is_none() && args.checkerboard.is_none() && !args.read_image_stdin {
+        bytes += monitors
+            .iter()
+            .map(|m| m.width * m.height * PIXEL)
+            .max()
+            .unwrap_or(0);
+    }
+
+    // the blur keeps a queue of linear pixels per thread
+    #[cfg(feature = "blur")]
+    {
+        let radius = match args.adaptive_blur {
+            Some(base) => monitors
+                .iter()
+                .map(|m| usize::from(adaptive_radius(base.get(), m.width, m.height)))
+                .max()
+                .unwrap_or(0),
+            None => args.radius.map_or(0, NonZeroUsize::get),
+        };
+        let threads = if cfg!(feature = "threads") {
+            num_cpus::get()
+        } else {
+            1
+        };
+        bytes += threads * (2 * radius + 1) * 4 * std::mem::size_of::<f32>();
+    }
+
+    // --kernel reads from a copy of the image
+    if args.kernel.is_some() {
+        bytes += width * height * PIXEL;
+    }
+
+    // the pixmap is uploaded from a copy
+    if args.apply_to_root {
+        bytes += width * height * PIXEL;
+    }
+
+    bytes
+}
+
+/// Run the steps of `--pipeline` on `screenshot`. Steps whose options weren't given are skipped.
+fn apply_effects(
+    screenshot: &mut ImgRefMut<BGRA8>,
+    args: &Cli,
+    monitors: &[Monitor],
+    icon: Option<ImgRef<BGRA8>>,
+) {
+    // how far the image is currently scaled down, monitors have to be scaled to match
+    let mut scaled = 1;
+
+    let steps = match args.pipeline.as_slice() {
+        [] if args.icon_blend_behind => BEHIND_PIPELINE,
+        [] => DEFAULT_PIPELINE,
+        steps => steps,
+    };
+
+    save_stage(screenshot, args, 1, "screenshot");
+    for (i, step) in steps.iter().enumerate() {
+        match step {
+            PipelineStep::ScaleDown if args.scale_up_only => {}
+            PipelineStep::ScaleUp if args.scale_down_only => {}
+            PipelineStep::ScaleDown => {
+                // scaling is unsafe
+                unsafe {
+                    time_routine!(screenshot, scale_down, args.factor, "scale");
+                }
+                if cfg!(feature = "scale") {
+                    scaled = args.factor.map_or(1, NonZeroUsize::get);
+                }
+            }
+            PipelineStep::Blur => {
+                let alpha: Option<Vec<u8>> = args
+                    .blur_alpha
+                    .then(|| screenshot.rows().flatten().map(|px| px.a).collect());
+                apply_blur(screenshot, args, monitors, scaled);
+                if let (Some(alpha), Some(radius)) = (alpha, args.blur_alpha_radius.or(args.radius))
+                {
+                    #[cfg(feature = "blur")]
+                    {
+                        timer_start!(blur_alpha);
+                        blur::blur_alpha(screenshot, &alpha, radius);
+                        timer_time!("Blurring alpha", blur_alpha);
+                    }
+                    #[cfg(not(feature = "blur"))]
+                    {
+                        let _ = (alpha, radius);
+                        warn_disabled!("blur");
+                    }
+                }
+            }
+            PipelineStep::ScaleUp => {
+                // scaling is unsafe
+                unsafe {
+                    time_routine!(screenshot, scale_up, args.factor, "scale");
+                }
+                scaled = 1;
+            }
+            PipelineStep::Pixelate => {
+                // scaling is unsafe
+                unsafe {
+                    time_routine!(
+                        screenshot,
+                        scale_down,
+                        args.pixelate,
+                        "scale",
+                        scale_up,
+                        args.pixelate,
+                        "scale"
+                    );
+                }
+            }
+            PipelineStep::AutoContrast => {
+                if args.auto_contrast {
+                    #[cfg(feature = "color-grading")]
+                    {
+                        timer_start!(auto_contrast);
+                        color::auto_contrast(screenshot);
+                        timer_time!("Stretching contrast", auto_contrast);
+                    }
+                    #[cfg(not(feature = "color-grading"))]
+                    warn_disabled!("color-grading");
+                }
+            }
+            PipelineStep::Brighten => {
+                time_routine!(
+                    screenshot,
+                    brighten,
+                    args.bright,
+                    "brightness",
+                    with args.channel
+                );
+            }
+            PipelineStep::Darken => {
+                time_routine!(
+                    screenshot,
+                    darken,
+                    args.dark,
+                    "brightness",
+                    with args.channel
+                );
+            }
+            PipelineStep::ColorMatrix => {
+                if let Some(matrix) = args.color_matrix {
+                    #[cfg(feature = "color-grading")]
+                    {
+                        timer_start!(color_matrix);
+                        color::apply_color_matrix(screenshot, matrix.0);
+                        timer_time!("Applying color matrix", color_matrix);
+                    }
+                    #[cfg(not(feature = "color-grading"))]
+                    {
+                        let _ = matrix;
+                        warn_disabled!("color-grading");
+                    }
+                }
+            }
+            PipelineStep::ColorBalance => {
+                if let Some(balance) = args.color_balance {
+                    #[cfg(feature = "color-grading")]
+                    {
+                        timer_start!(color_balance);
+                        color::color_balance(
+                            screenshot,
+                            balance.shadows,
+                            balance.midtones,
+                            balance.highlights,
+                        );
+                        timer_time!("Balancing colors", color_balance);
+                    }
+                    #[cfg(not(feature = "color-grading"))]
+                    {
+                        let _ = balance;
+                        warn_disabled!("color-grading");
+                    }
+                }
+            }
+            PipelineStep::Quantize => {
+                if let Some(n) = args.color_quantize {
+                    #[cfg(feature = "quantize")]
+                    {
+                        timer_start!(quantize);
+                        color::quantize(screenshot, n.get());
+                        timer_time!("Quantizing colors", quantize);
+                    }
+                    #[cfg(not(feature = "quantize"))]
+                    {
+                        let _ = n;
+                        warn_disabled!("quantize");
+                    }
+                }
+            }
+            PipelineStep::Desaturate => {
+                if let Some(range) = args.desaturate_except {
+                    #[cfg(feature = "color-grading")]
+                    {
+                        timer_start!(desaturate);
+                        color::desaturate_except(screenshot, range.from, range.to);
+                        timer_time!("Desaturating", desaturate);
+                    }
+                    #[cfg(not(feature = "color-grading"))]
+                    {
+                        let _ = range;
+                        warn_disabled!("color-grading");
+                    }
+                }
+            }
+            PipelineStep::Dye => {
+                if !args.dye.is_empty() {
+                    #[cfg(feature = "color-grading")]
+                    {
+                        timer_start!(dye);
+                        color::dye(screenshot, &args.dye);
+                        timer_time!("Dyeing", dye);
+                    }
+                    #[cfg(not(feature = "color-grading"))]
+                    warn_disabled!("color-grading");
+                }
+            }
+            PipelineStep::Kernel => {
+                if let Some(ref kernel) = args.kernel {
+                    timer_start!(convolve);
+                    let mut values = kernel.values.clone();
+                    if args.kernel_normalize {
+                        filters::normalize(&mut values);
+                    }
+                    filters::convolve(screenshot, &values, kernel.width);
+                    timer_time!("Convolving", convolve);
+                }
+            }
+            PipelineStep::Shadow => {
+                if args.shadow_overlay {
+                    draw_shadows(screenshot, monitors);
+                }
+            }
+            PipelineStep::Mirror => {
+                if !args.mirror_x.is_empty() || !args.mirror_y.is_empty() {
+                    mirror_monitors(screenshot, args, monitors);
+                }
+            }
+            PipelineStep::Overlay => {
+                #[cfg(any(feature = "png", feature = "jpeg"))]
+                overlay_icon(screenshot, args, monitors, icon);
+                #[cfg(not(any(feature = "png", feature = "jpeg")))]
+                {
+                    let _ = icon;
+                    if args.invert {
+                        warn_disabled!("invert");
+                    }
+                }
+            }
+        }
+        save_stage(screenshot, args, i + 2, &step.to_string().replace('-', "_"));
+    }
+}
+
+/// Save `screenshot` as `<n>_<name>.png` in the `--debug-save-stages` directory.
+fn save_stage(screenshot: &ImgRefMut<BGRA8>, args: &Cli, n: usize, name: &str) {
+    #[cfg(feature = "debug-stages")]
+    if let Some(ref dir) = args.debug_save_stages {
+        let path = dir.join(format!("{:02}_{}.png", n, name));
+        let saved = std::fs::create_dir_all(dir)
+            .map_err(Box::<dyn Error>::from)
+            .and_then(|()| output::write_png(&path, screenshot.as_ref()));
+        // only for debugging, not worth failing the lock over
+        if let Err(e) = saved {
+            warn!("Couldn't save {}: {}", path.display(), e);
+        }
+    }
+    #[cfg(not(feature = "debug-stages"))]
+    let _ = (screenshot, args, n, name);
+}
+
+/// Refuse blur radii above `MAX_BLUR_RADIUS`, or `--max-blur-radius-override` if given.
+fn check_blur_radius(args: &Cli) -> Result<(), String> {
+    let max = args
+        .max_blur_radius_override
+        .map_or(MAX_BLUR_RADIUS, NonZeroUsize::get);
+    let (option, radius) = match (args.radius, args.adaptive_blur, args.gaussian_sigma) {
+        (Some(radius), _, _) => ("--blur", radius.get()),
+        (None, Some(base), _) => ("--adaptive-blur", usize::from(base.get())),
+        (None, None, Some(sigma)) => ("--gaussian-sigma's radius", (3.0 * sigma).ceil() as usize),
+        (None, None, None) => return Ok(()),
+    };
+    if radius > max {
+        return Err(format!(
+            "{} {} is larger than the maximum blur radius of {}, blurring would take very long. \
+             Use --scale for stronger blurs, or raise the limit with --max-blur-radius-override.",
+            option, radius, max
+        ));
+    }
+    Ok(())
+}
+
+/// Every problem with the combination of arguments that can be found without
+/// looking at the screen. Monitor indices are only checked once monitors are known.
+fn validate_args(args: &Cli) -> Result<(), Vec<String>> {
+    let mut problems = Vec::new();
+    if let Err(e) = check_blur_radius(args) {
+        problems.push(e);
+    }
+    if args.detect_retina && args.radius.is_some_and(|r| r.get() > usize::from(u8::MAX)) {
+        problems.push("--detect-retina only supports a --blur radius of up to 255".to_owned());
+    }
+    if args.blur_alpha && args.blur_alpha_radius.or(args.radius).is_none() {
+        problems.push("--blur-alpha needs --blur-alpha-radius or --blur".to_owned());
+    }
+    // the --scale-*-only options skip one of the steps on purpose
+    if args.factor.is_some() && !args.scale_down_only && !args.scale_up_only {
+        if let Err(e) = check_pipeline(&args.pipeline) {
+            problems.push(e);
         }
+    }
+    if problems.is_empty() {
+        Ok(())
     } else {
-        match cmd.try_wait() {
-            Ok(None) => Ok(()),
-            Ok(Some(status)) => status_to_result(status),
-            Err(e) => Err(e.into()),
+        Err(problems)
+    }
+}
+
+/// Check that `steps` scale down and back up in that order, and don't run anything
+/// placed by monitor position in between, where the image is smaller than the screen.
+fn check_pipeline(steps: &[PipelineStep]) -> Result<(), String> {
+    let mut scaled = false;
+    for step in steps {
+        match step {
+            PipelineStep::ScaleDown if scaled => {
+                return Err("--pipeline scales down twice without scaling up in between".into())
+            }
+            PipelineStep::ScaleDown => scaled = true,
+            PipelineStep::ScaleUp if !scaled => {
+                return Err("--pipeline scales up without scaling down first".into())
+            }
+            PipelineStep::ScaleUp => scaled = false,
+            PipelineStep::Overlay | PipelineStep::Shadow | PipelineStep::Mirror if scaled => {
+                return Err(format!(
+                    "--pipeline runs {} between scale-down and scale-up, \
+                     where the monitors' positions don't match the image",
+                    step
+                ))
+            }
+            _ => {}
+        }
+    }
+    if scaled {
+        Err("--pipeline scales down without scaling back up".into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Blur the whole image with `--blur`, or each monitor on its own with `--adaptive-blur`
+/// or `--detect-retina`. `factor` is how far the image is currently scaled down.
+fn apply_blur(screenshot: &mut ImgRefMut<BGRA8>, args: &Cli, monitors: &[Monitor], factor: usize) {
+    if let Some(sigma) = args.gaussian_sigma {
+        #[cfg(feature = "blur")]
+        {
+            timer_start!(gaussian);
+            blur::blur_gaussian_sigma(screenshot, sigma);
+            timer_time!("Gaussian blur", gaussian);
+        }
+        #[cfg(not(feature = "blur"))]
+        {
+            let _ = sigma;
+            warn_disabled!("blur");
+        }
+        return;
+    }
+    if args.adaptive_blur.is_none() && !args.detect_retina {
+        time_routine!(screenshot, blur, args.radius, "blur");
+        return;
+    }
+    if args.adaptive_blur.is_none() && args.radius.is_none() {
+        return;
+    }
+
+    #[cfg(feature = "blur")]
+    {
+        let bounds = ClipRect::new(0, 0, screenshot.width(), screenshot.height());
+        let mut rects: Vec<ClipRect> = Vec::new();
+        for m in monitors {
+            let rect = ClipRect::new(
+                m.x / factor,
+                m.y / factor,
+                m.width / factor,
+                m.height / factor,
+            );
+            // mirrored monitors share pixels, only blur them once
+            let rect = match rect.intersect(&bounds) {
+                Some(rect) if !rects.iter().any(|r| r.overlaps(&rect)) => rect,
+                _ => continue,
+            };
+            rects.push(rect);
+
+            let radius = match (args.adaptive_blur, args.radius) {
+                (Some(base), _) => usize::from(adaptive_radius(base.get(), m.width, m.height)),
+                (None, Some(radius)) => radius.get(),
+                (None, None) => unreachable!(),
+            };
+            let radius = match m.dpi() {
+                // validate_args keeps the radius within a u8 with --detect-retina
+                Some(dpi) if args.detect_retina => {
+                    let radius = u8::try_from(radius).unwrap_or(u8::MAX);
+                    usize::from(dpi_scale_radius(radius, dpi, args.dpi_threshold))
+                }
+                _ => radius,
+            };
+
+            let timer = Instant::now();
+            let mut view = screenshot.sub_image_mut(rect.x, rect.y, rect.width, rect.height);
+            view.blur(NonZeroUsize::new(radius).unwrap());
+            debug!(
+                "Blurring {}x{} monitor {} with radius {} took {:#?}",
+                m.width,
+                m.height,
+                m.index,
+                radius,
+                timer.elapsed()
+            );
         }
     }
+
+    #[cfg(not(feature = "blur"))]
+    {
+        let _ = (monitors, factor);
+        warn_disabled!("blur");
+    }
+}
+
+fn draw_shadows(screenshot: &mut ImgRefMut<BGRA8>, monitors: &[Monitor]) {
+    timer_start!(shadow);
+    for m in monitors {
+        algorithms::draw_bottom_shadow(screenshot, m.rect(), m.height / 10, 0.6);
+    }
+    timer_time!("Drawing shadows", shadow);
+}
+
+/// Flip the monitors picked by `--mirror-x` and `--mirror-y`.
+fn mirror_monitors(screenshot: &mut ImgRefMut<BGRA8>, args: &Cli, monitors: &[Monitor]) {
+    timer_start!(mirror);
+    let bounds = ClipRect::new(0, 0, screenshot.width(), screenshot.height());
+    for m in monitors {
+        let mode = match (
+            args.mirror_x.contains(&m.index),
+            args.mirror_y.contains(&m.index),
+        ) {
+            (true, true) => FlipMode::Both,
+            (true, false) => FlipMode::Horizontal,
+            (false, true) => FlipMode::Vertical,
+            (false, false) => continue,
+        };
+        if let Some(r) = m.rect().intersect(&bounds) {
+            algorithms::flip(
+                &mut screenshot.sub_image_mut(r.x, r.y, r.width, r.height),
+                mode,
+            );
+        }
+    }
+    timer_time!("Mirroring monitors", mirror);
+}
+
+/// Decode the icon file, or download it with the `remote-icon` feature.
+/// `None` if the download failed and there's no cached copy.
+#[cfg(any(feature = "png", feature = "jpeg"))]
+fn decode_icon(path: &Path, args: &Cli) -> Result<Option<imagefmt::Image<u8>>, Box<dyn Error>> {
+    timer_start!(decode);
+    #[cfg(feature = "remote-icon")]
+    let image = match path.to_str().filter(|p| remote::is_url(p)) {
+        Some(url) => match remote::fetch(url, Duration::from_secs(args.icon_cache_ttl)) {
+            Ok(bytes) => imagefmt::read_from(&mut io::Cursor::new(bytes), ColFmt::BGRA)?,
+            // an unreachable server must never keep the screen from locking
+            Err(e) => {
+                warn!("Couldn't download {}, locking without the icon: {}", url, e);
+                return Ok(None);
+            }
+        },
+        None => decode_file(path)?,
+    };
+    #[cfg(not(feature = "remote-icon"))]
+    let image = decode_file(path)?;
+    #[cfg(not(feature = "remote-icon"))]
+    let _ = args;
+    timer_time!("Decoding overlay image", decode);
+    Ok(Some(image))
+}
+
+/// Get the decoded icon ready to be overlaid.
+#[cfg(any(feature = "png", feature = "jpeg"))]
+fn load_icon(image: imagefmt::Image<u8>, args: &Cli) -> Result<ImgVec<BGRA8>, Box<dyn Error>> {
+    let image = match args.pad_icon {
+        Some(pixels) => algorithms::pad_icon(&image, pixels),
+        None => image,
+    };
+    let mut image = ImgVec::new(image.buf.as_bgra().to_vec(), image.w, image.h);
+    if args.premultiplied {
+        overlay::unpremultiply(image.buf_mut());
+    }
+    if let Some(threshold) = args.icon_mask {
+        overlay::threshold_alpha(image.buf_mut(), threshold);
+    }
+    if let Some(factor) = args.icon_alpha_multiply {
+        for px in image.buf_mut().iter_mut() {
+            px.a = (f32::from(px.a) * factor).round().min(255.0) as u8;
+        }
+    }
+    if let Some(color) = args.icon_colorize {
+        overlay::colorize(image.buf_mut(), color);
+    }
+
+    if let Some(mode) = args.icon_flip {
+        algorithms::flip(&mut image.as_mut(), mode);
+    }
+
+    #[cfg(feature = "scale")]
+    let image = match args.icon_scale {
+        Some(factor) => {
+            timer_start!(icon_scale);
+            let w = (image.width() as f32 * factor).round().max(1.0) as usize;
+            let h = (image.height() as f32 * factor).round().max(1.0) as usize;
+            let scaled = resize(image.as_ref(), w, h, args.scale_quality);
+            timer_time!("Scaling overlay image", icon_scale);
+            scaled
+        }
+        None => image,
+    };
+    #[cfg(not(feature = "scale"))]
+    if args.icon_scale.is_some() {
+        warn_disabled!("scale");
+    }
+
+    let image = match args.icon_rotate {
+        Some(degrees) => {
+            timer_start!(icon_rotate);
+            let rotated = transform::rotate(image.as_ref(), degrees);
+            timer_time!("Rotating overlay image", icon_rotate);
+            rotated
+        }
+        None => image,
+    };
+
+    let image = match args.max_icon_size {
+        Some(max) => clamp_icon_size(image, max.width.get(), max.height.get(), args.scale_quality),
+        None => image,
+    };
+    Ok(image)
+}
+
+/// Scale `icon` down to fit in `max_w`x`max_h`, keeping its aspect ratio.
+#[cfg(any(feature = "png", feature = "jpeg"))]
+fn clamp_icon_size(
+    icon: ImgVec<BGRA8>,
+    max_w: usize,
+    max_h: usize,
+    quality: cli::ScaleQuality,
+) -> ImgVec<BGRA8> {
+    let (w, h) = (icon.width(), icon.height());
+    if w <= max_w && h <= max_h {
+        return icon;
+    }
+    let factor = (max_w as f32 / w as f32).min(max_h as f32 / h as f32);
+    let fit = |n: usize, max: usize| ((n as f32 * factor).round() as usize).clamp(1, max);
+    debug!(
+        "Icon is {}x{}, scaling it down to fit in {}x{}",
+        w, h, max_w, max_h
+    );
+    resize(icon.as_ref(), fit(w, max_w), fit(h, max_h), quality)
+}
+
+/// Resize `img` to `width`x`height`, falling back to bilinear without the `scale` feature.
+#[cfg(any(feature = "png", feature = "jpeg"))]
+fn resize(
+    img: ImgRef<BGRA8>,
+    width: usize,
+    height: usize,
+    quality: cli::ScaleQuality,
+) -> ImgVec<BGRA8> {
+    match quality {
+        #[cfg(feature = "scale")]
+        cli::ScaleQuality::Nearest => scale::resize(img, width, height),
+        _ => transform::resize(img, width, height),
+    }
+}
+
+/// Overlay or invert `icon` on each monitor, or invert everything without an icon.
+#[cfg(any(feature = "png", feature = "jpeg"))]
+fn overlay_icon(
+    screenshot: &mut ImgRefMut<BGRA8>,
+    args: &Cli,
+    monitors: &[Monitor],
+    icon: Option<ImgRef<BGRA8>>,
+) {
+    if let Some(image) = icon {
+        // the whole pixel part goes through wrap_to_screen, the rest is baked into the icon
+        let (fx, fy) = match args.pos[..] {
+            [px, py] => (px - px.floor(), py - py.floor()),
+            _ => (0.0, 0.0),
+        };
+        let shifted = (fx != 0.0 || fy != 0.0).then(|| transform::shift(image, fx, fy));
+        let image = shifted.as_ref().map_or(image, ImgVec::as_ref);
+
+        for rect in monitors
+            .iter()
+            .filter(|m| !args.ignore.contains(&m.index))
+            .map(Monitor::rect)
+        {
+            let ClipRect {
+                x,
+                y,
+                width: w,
+                height: h,
+            } = rect;
+            let positions: Vec<(usize, usize)> = if let Some(n) = args.icon_repeat_diagonal {
+                let n = n.get();
+                (0..n).map(|i| (x + i * w / n, y + i * h / n)).collect()
+            } else if args.center_on_cursor && !args.pos.is_empty() {
+                // a position on the whole screen, only drawn on the monitor with the cursor
+                let (px, py) = (args.pos[0] as usize, args.pos[1] as usize);
+                let center = ClipRect::new(px + image.width() / 2, py + image.height() / 2, 1, 1);
+                if rect.contains(&center) {
+                    vec![(px, py)]
+                } else {
+                    Vec::new()
+                }
+            } else if args.pos.is_empty() {
+                // only bigger than the monitor with --force-large-icon
+                vec![(
+                    (w / 2 + x).saturating_sub(image.width() / 2),
+                    (h / 2 + y).saturating_sub(image.height() / 2),
+                )]
+            } else {
+                unsafe {
+                    vec![(
+                        wrap_to_screen(args.pos.get_unchecked(0).floor() as isize, w + x),
+                        wrap_to_screen(args.pos.get_unchecked(1).floor() as isize, h + y),
+                    )]
+                }
+            };
+
+            for (x_off, y_off) in positions {
+                debug!(
+                    "Calculated image position on monitor: ({},{})",
+                    x_off, y_off
+                );
+
+                timer_start!(overlay);
+                let clip = args.clamp_icon_to_monitor.then_some(rect);
+                if args.invert {
+                    screenshot.invert(Some(image), x_off, y_off, clip);
+                } else {
+                    screenshot.compose(image, x_off, y_off, clip);
+                }
+                if let Some(outline) = args.icon_outline {
+                    let (r, g, b) = outline.color;
+                    algorithms::draw_icon_outline(
+                        screenshot,
+                        image,
+                        x_off,
+                        y_off,
+                        outline.width.get(),
+                        BGRA8 { b, g, r, a: 255 },
+                        clip,
+                    );
+                }
+                timer_time!("Overlaying image", overlay);
+            }
+        }
+    } else if args.invert {
+        timer_start!(invert);
+        screenshot.invert(None, 0, 0, None);
+        timer_time!("Inverting image", invert);
+    }
+}
+
+/// Double `r` on monitors denser than `threshold` DPI, where the same radius looks half as strong.
+#[cfg(feature = "blur")]
+fn dpi_scale_radius(r: u8, dpi: f32, threshold: f32) -> u8 {
+    if dpi > threshold {
+        r.saturating_mul(2)
+    } else {
+        r
+    }
+}
+
+/// Scale `base` so that a monitor's blur looks the same as it would on a 1080p one.
+#[cfg(feature = "blur")]
+fn adaptive_radius(base: u8, w: usize, h: usize) -> u8 {
+    let scale = ((w * h) as f64 / (1920.0 * 1080.0)).sqrt();
+    (f64::from(base) * scale).round().clamp(1.0, 255.0) as u8
+}
+
+/// Pixel format byte of the raw header, the only one we write.
+const RAW_FORMAT_BGRA8: u8 = 0;
+
+/// Header for `--format-raw-header`, 17 bytes:
+/// `I3LOCKR\0`, the width and height as little endian u32s, then the pixel format.
+/// Unpadded rows follow.
+fn write_raw_header<W: Write>(
+    out: &mut W,
+    width: usize,
+    height: usize,
+    format: u8,
+) -> io::Result<()> {
+    let size = |n: usize| {
+        u32::try_from(n).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "image too large"))
+    };
+    out.write_all(b"I3LOCKR\0")?;
+    out.write_all(&size(width)?.to_le_bytes())?;
+    out.write_all(&size(height)?.to_le_bytes())?;
+    out.write_all(&[format])
 }
 
 fn status_to_result(status: ExitStatus) -> Result<(), Box<dyn Error>> {
@@ -342,6 +1814,121 @@ where
 mod tests {
     use super::*;
 
+    #[test]
+    fn memory() {
+        let args = Cli::from_iter_safe(["i3lockr"]).unwrap();
+        let monitors = [Monitor::covering(1920, 1080)];
+        assert_eq!(
+            estimate_memory(&args, &monitors, 1920, 1080),
+            2 * 1920 * 1080 * 4
+        );
+
+        let args = Cli::from_iter_safe(["i3lockr", "--blur=10"]).unwrap();
+        let blurred = estimate_memory(&args, &monitors, 1920, 1080);
+        assert_eq!(blurred > 2 * 1920 * 1080 * 4, cfg!(feature = "blur"));
+    }
+
+    #[test]
+    fn raw_header() {
+        let mut out = Vec::new();
+        write_raw_header(&mut out, 1920, 1080, RAW_FORMAT_BGRA8).unwrap();
+        assert_eq!(out.len(), 17);
+        assert_eq!(&out[..8], b"I3LOCKR\0");
+        assert_eq!(out[8..12], 1920u32.to_le_bytes());
+        assert_eq!(out[12..16], 1080u32.to_le_bytes());
+        assert_eq!(out[16], 0);
+    }
+
+    #[test]
+    fn pipeline() {
+        let args = Cli::from_iter_safe(["i3lockr", "--pipeline=blur,darken,overlay"]).unwrap();
+        assert_eq!(
+            args.pipeline,
+            [
+                PipelineStep::Blur,
+                PipelineStep::Darken,
+                PipelineStep::Overlay
+            ]
+        );
+        assert!(Cli::from_iter_safe(["i3lockr", "--pipeline=blur,sharpen"]).is_err());
+    }
+
+    #[test]
+    #[cfg(all(feature = "self-test", feature = "scale", feature = "brightness"))]
+    fn self_test() {
+        assert_eq!(super::self_test(), SELF_TEST_HASH);
+    }
+
+    #[test]
+    fn blur_limit() {
+        let args = Cli::from_iter_safe(["i3lockr", "--blur=50"]).unwrap();
+        assert!(check_blur_radius(&args).is_ok());
+        let args = Cli::from_iter_safe(["i3lockr", "--blur=51"]).unwrap();
+        assert!(check_blur_radius(&args).is_err());
+        let args = Cli::from_iter_safe(["i3lockr", "--blur=200", "--max-blur-radius-override=255"])
+            .unwrap();
+        assert!(check_blur_radius(&args).is_ok());
+    }
+
+    #[cfg(any(feature = "png", feature = "jpeg"))]
+    #[test]
+    fn clamp_icon() {
+        let icon = || ImgVec::new(vec![BGRA8::default(); 400 * 200], 400, 200);
+        let quality = cli::ScaleQuality::Bilinear;
+        let clamped = clamp_icon_size(icon(), 100, 100, quality);
+        assert_eq!((clamped.width(), clamped.height()), (100, 50));
+        let clamped = clamp_icon_size(icon(), 1000, 1000, quality);
+        assert_eq!((clamped.width(), clamped.height()), (400, 200));
+    }
+
+    #[test]
+    fn pipeline_order() {
+        let (down, up) = (PipelineStep::ScaleDown, PipelineStep::ScaleUp);
+        let (blur, overlay) = (PipelineStep::Blur, PipelineStep::Overlay);
+
+        assert!(check_pipeline(DEFAULT_PIPELINE).is_ok());
+        assert!(check_pipeline(BEHIND_PIPELINE).is_ok());
+        assert!(check_pipeline(&[blur, overlay]).is_ok());
+        assert!(check_pipeline(&[down, blur]).is_err());
+        assert!(check_pipeline(&[up, blur, down]).is_err());
+        assert!(check_pipeline(&[down, down, up]).is_err());
+        assert!(check_pipeline(&[down, overlay, up]).is_err());
+    }
+
+    #[test]
+    fn validate() {
+        let args =
+            Cli::from_iter_safe(["i3lockr", "--blur=10", "--invert", "--icon=x.png"]).unwrap();
+        assert_eq!(validate_args(&args), Ok(()));
+        let args = Cli::from_iter_safe(["i3lockr", "--gaussian-sigma=20", "--blur-alpha"]).unwrap();
+        assert_eq!(validate_args(&args).unwrap_err().len(), 2);
+        let args = Cli::from_iter_safe([
+            "i3lockr",
+            "--blur=300",
+            "--max-blur-radius-override=300",
+            "--detect-retina",
+        ])
+        .unwrap();
+        assert_eq!(validate_args(&args).unwrap_err().len(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "blur")]
+    fn dpi() {
+        assert_eq!(dpi_scale_radius(10, 96.0, 150.0), 10);
+        assert_eq!(dpi_scale_radius(10, 220.0, 150.0), 20);
+        assert_eq!(dpi_scale_radius(200, 220.0, 150.0), 255);
+    }
+
+    #[test]
+    #[cfg(feature = "blur")]
+    fn adaptive() {
+        assert_eq!(adaptive_radius(10, 1920, 1080), 10);
+        assert_eq!(adaptive_radius(10, 3840, 2160), 20);
+        assert_eq!(adaptive_radius(1, 640, 480), 1);
+        assert_eq!(adaptive_radius(200, 7680, 4320), 255);
+    }
+
     #[test]
     fn nofork() {
         assert!(forking(
@@ -0,0 +1,174 @@
+use std::thread::sleep;
+use std::time::Duration;
+
+use log::debug;
+
+use xcb::{randr, xinerama};
+use xcb::{Connection, Xid};
+
+use crate::cli::MonitorOrder;
+use crate::types::ClipRect;
+
+/// An active CRTC.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct Monitor {
+    /// Position in the enumeration order, this is what `--ignore-monitors` refers to.
+    pub index: usize,
+    /// Name of the first output driven by this CRTC, e.g. "HDMI-1".
+    pub name: String,
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    /// Physical size reported by the output, 0 if unknown.
+    pub mm_width: u32,
+    pub mm_height: u32,
+    pub primary: bool,
+}
+
+impl Monitor {
+    /// A stand-in for when there's no X server to ask, covers the whole image.
+    pub fn covering(width: usize, height: usize) -> Self {
+        Self {
+            index: 0,
+            name: String::new(),
+            x: 0,
+            y: 0,
+            width,
+            height,
+            mm_width: 0,
+            mm_height: 0,
+            primary: true,
+        }
+    }
+
+    /// The part of the screenshot this monitor covers.
+    pub const fn rect(&self) -> ClipRect {
+        ClipRect::new(self.x, self.y, self.width, self.height)
+    }
+
+    /// Horizontal pixels per inch, if the physical size is known.
+    pub fn dpi(&self) -> Option<f32> {
+        (self.mm_width > 0).then(|| self.width as f32 * 25.4 / self.mm_width as f32)
+    }
+}
+
+/// Query RandR for the active monitors on `screen_num`.
+pub fn query(conn: &Connection, screen_num: i32, order: MonitorOrder) -> xcb::Result<Vec<Monitor>> {
+    let screen = conn
+        .get_setup()
+        .roots()
+        .nth(screen_num as usize)
+        .unwrap_or_else(|| unreachable!());
+
+    let cookie = conn.send_request(&randr::GetScreenResources {
+        window: screen.root(),
+    });
+    let reply = conn.wait_for_reply(cookie)?;
+
+    let cookie = conn.send_request(&randr::GetOutputPrimary {
+        window: screen.root(),
+    });
+    let primary = conn.wait_for_reply(cookie)?.output();
+
+    let mut crtcs: Vec<_> = reply
+        .crtcs()
+        .iter()
+        .filter_map(|crtc| {
+            let cookie = conn.send_request(&randr::GetCrtcInfo {
+                crtc: *crtc,
+                config_timestamp: reply.timestamp(),
+            });
+            conn.wait_for_reply(cookie).ok()
+        })
+        .collect();
+
+    if order == MonitorOrder::Physical {
+        // disabled CRTCs report a position of 0,0, keep them out of the way
+        crtcs.sort_by_key(|m| (m.mode().is_none(), m.x(), m.y()));
+    }
+
+    Ok(crtcs
+        .iter()
+        .enumerate()
+        .filter(|(_, m)| !m.mode().is_none())
+        .map(|(index, m)| {
+            let info = m.outputs().first().and_then(|output| {
+                let cookie = conn.send_request(&randr::GetOutputInfo {
+                    output: *output,
+                    config_timestamp: reply.timestamp(),
+                });
+                conn.wait_for_reply(cookie).ok()
+            });
+            Monitor {
+                index,
+                name: info
+                    .as_ref()
+                    .map(|info| String::from_utf8_lossy(info.name()).into_owned())
+                    .unwrap_or_default(),
+                x: m.x() as usize,
+                y: m.y() as usize,
+                width: usize::from(m.width()),
+                height: usize::from(m.height()),
+                mm_width: info.as_ref().map_or(0, |info| info.mm_width()),
+                mm_height: info.as_ref().map_or(0, |info| info.mm_height()),
+                primary: m.outputs().contains(&primary),
+            }
+        })
+        .collect())
+}
+
+/// `query`, asked again up to `retries` times 100 ms apart until it finds an active monitor.
+/// RandR can take a moment to catch up after a monitor is plugged in.
+pub fn query_retry(
+    conn: &Connection,
+    screen_num: i32,
+    order: MonitorOrder,
+    retries: usize,
+) -> xcb::Result<Vec<Monitor>> {
+    let mut result = query(conn, screen_num, order);
+    for attempt in 1..=retries {
+        match result {
+            Ok(ref monitors) if !monitors.is_empty() => break,
+            Ok(_) => debug!(
+                "RandR reported no active monitors, retrying ({}/{})",
+                attempt, retries
+            ),
+            Err(ref e) => debug!(
+                "Querying RandR failed ({}), retrying ({}/{})",
+                e, attempt, retries
+            ),
+        }
+        sleep(Duration::from_millis(100));
+        result = query(conn, screen_num, order);
+    }
+    result
+}
+
+/// Query Xinerama for the monitors, it knows nothing about names, physical sizes or the primary.
+pub fn query_xinerama(conn: &Connection, order: MonitorOrder) -> xcb::Result<Vec<Monitor>> {
+    let cookie = conn.send_request(&xinerama::QueryScreens {});
+    let reply = conn.wait_for_reply(cookie)?;
+
+    let mut screens = reply.screen_info().to_vec();
+    if order == MonitorOrder::Physical {
+        screens.sort_by_key(|s| (s.x_org, s.y_org));
+    }
+
+    Ok(screens
+        .iter()
+        .enumerate()
+        .map(|(index, s)| Monitor {
+            index,
+            name: String::new(),
+            x: s.x_org.max(0) as usize,
+            y: s.y_org.max(0) as usize,
+            width: usize::from(s.width),
+            height: usize::from(s.height),
+            mm_width: 0,
+            mm_height: 0,
+            primary: index == 0,
+        })
+        .collect())
+}
@@ -0,0 +1,21 @@
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// Show `summary` as a desktop notification through `notify-send` for `timeout`.
+///
+/// Each notification replaces the previous one on daemons that understand the
+/// synchronous hint, e.g. dunst.
+pub fn send(summary: &str, timeout: Duration) -> Result<(), String> {
+    let status = Command::new("notify-send")
+        .args(["--app-name", env!("CARGO_PKG_NAME")])
+        .args(["--hint", "string:x-canonical-private-synchronous:i3lockr"])
+        .arg(format!("--expire-time={}", timeout.as_millis()))
+        .arg(summary)
+        .stdin(Stdio::null())
+        .status();
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(format!("notify-send exited with {}", status)),
+        Err(e) => Err(format!("Couldn't run notify-send: {}", e)),
+    }
+}
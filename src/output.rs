@@ -0,0 +1,247 @@
+#[cfg(feature = "png")]
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "scrot")]
+use chrono::{Datelike, Timelike};
+
+#[cfg(feature = "png")]
+use imagefmt::{ColFmt, ColType};
+
+#[cfg(feature = "gzip")]
+use flate2::{write::GzEncoder, Compression};
+
+use imgref::ImgRef;
+
+use rgb::alt::BGRA8;
+use rgb::ComponentBytes;
+
+use crate::cli::{OutputFormat, PixelFormat};
+
+pub fn write<W: Write>(out: &mut W, img: ImgRef<BGRA8>, format: OutputFormat) -> io::Result<()> {
+    match format {
+        OutputFormat::Raw => write_raw(out, img),
+        OutputFormat::Ppm => write_ppm(out, img),
+        OutputFormat::Bmp => write_bmp(out, img),
+    }
+}
+
+/// Raw BGRA bytes, the same thing we hand to i3lock.
+pub fn write_raw<W: Write>(out: &mut W, img: ImgRef<BGRA8>) -> io::Result<()> {
+    for row in img.rows() {
+        out.write_all(row.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Raw pixels in `format`, `Bgra8` is the same as `write_raw`.
+pub fn write_pixels<W: Write>(
+    out: &mut W,
+    img: ImgRef<BGRA8>,
+    format: PixelFormat,
+) -> io::Result<()> {
+    if format == PixelFormat::Bgra8 {
+        return write_raw(out, img);
+    }
+
+    let mut line = Vec::with_capacity(img.width() * format.bytes_per_pixel());
+    for row in img.rows() {
+        line.clear();
+        for px in row {
+            match format {
+                PixelFormat::Bgra8 => line.extend([px.b, px.g, px.r, px.a]),
+                PixelFormat::Rgba8 => line.extend([px.r, px.g, px.b, px.a]),
+                PixelFormat::Rgb8 => line.extend([px.r, px.g, px.b]),
+                PixelFormat::Bgr8 => line.extend([px.b, px.g, px.r]),
+            }
+        }
+        out.write_all(&line)?;
+    }
+    Ok(())
+}
+
+/// `write_pixels`, gzip-compressed.
+#[cfg(feature = "gzip")]
+pub fn write_gzip<W: Write>(
+    out: &mut W,
+    img: ImgRef<BGRA8>,
+    format: PixelFormat,
+) -> io::Result<()> {
+    let mut enc = GzEncoder::new(out, Compression::fast());
+    write_pixels(&mut enc, img, format)?;
+    enc.finish().map(drop)
+}
+
+/// Binary PPM (P6), 8 bits per channel. Alpha is dropped.
+pub fn write_ppm<W: Write>(out: &mut W, img: ImgRef<BGRA8>) -> io::Result<()> {
+    write!(out, "P6\n{} {}\n255\n", img.width(), img.height())?;
+
+    let mut line = Vec::with_capacity(img.width() * 3);
+    for row in img.rows() {
+        line.clear();
+        line.extend(row.iter().flat_map(|px| [px.r, px.g, px.b]));
+        out.write_all(&line)?;
+    }
+    Ok(())
+}
+
+/// PNG file at `path`, alpha is dropped.
+#[cfg(feature = "png")]
+pub fn write_png(path: &Path, img: ImgRef<BGRA8>) -> Result<(), Box<dyn Error>> {
+    let mut bytes = Vec::with_capacity(img.width() * img.height() * 4);
+    write_raw(&mut bytes, img)?;
+    imagefmt::write(
+        path,
+        img.width(),
+        img.height(),
+        ColFmt::BGRA,
+        &bytes,
+        ColType::Color,
+    )?;
+    Ok(())
+}
+
+/// Expand scrot's `%Y`, `%m`, `%d`, `%H`, `%M` and `%S` in `path` to `time`, and `%%` to `%`.
+/// Anything else is left as is, as are paths that aren't UTF-8.
+#[cfg(feature = "scrot")]
+pub fn expand_scrot_path<T: Datelike + Timelike>(path: &Path, time: &T) -> PathBuf {
+    let Some(s) = path.to_str() else {
+        return path.to_path_buf();
+    };
+
+    let mut out = String::with_capacity(s.len() + 16);
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('Y') => out.push_str(&format!("{:04}", time.year())),
+            Some('m') => out.push_str(&format!("{:02}", time.month())),
+            Some('d') => out.push_str(&format!("{:02}", time.day())),
+            Some('H') => out.push_str(&format!("{:02}", time.hour())),
+            Some('M') => out.push_str(&format!("{:02}", time.minute())),
+            Some('S') => out.push_str(&format!("{:02}", time.second())),
+            Some('%') => out.push('%'),
+            Some(other) => {
+                out.push('%');
+                out.push(other);
+            }
+            None => out.push('%'),
+        }
+    }
+    PathBuf::from(out)
+}
+
+/// BMP file at `path`, see `write_bmp`.
+pub fn save_bmp(path: &Path, img: ImgRef<BGRA8>) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write_bmp(&mut out, img)?;
+    out.flush()
+}
+
+const BMP_FILE_HEADER: u32 = 14;
+const BMP_V4_HEADER: u32 = 108;
+
+/// 32-bit BMP with a BITMAPV4HEADER. Rows are stored bottom-up as per the spec.
+/// The screenshot is always opaque, so alpha is written as 255.
+pub fn write_bmp<W: Write>(out: &mut W, img: ImgRef<BGRA8>) -> io::Result<()> {
+    let (w, h) = (img.width() as u32, img.height() as u32);
+    let image_size = w * h * 4;
+    let offset = BMP_FILE_HEADER + BMP_V4_HEADER;
+
+    // BITMAPFILEHEADER
+    out.write_all(b"BM")?;
+    out.write_all(&(offset + image_size).to_le_bytes())?;
+    out.write_all(&0u32.to_le_bytes())?; // reserved
+    out.write_all(&offset.to_le_bytes())?;
+
+    // BITMAPV4HEADER
+    out.write_all(&BMP_V4_HEADER.to_le_bytes())?;
+    out.write_all(&(w as i32).to_le_bytes())?;
+    out.write_all(&(h as i32).to_le_bytes())?; // positive height means bottom-up
+    out.write_all(&1u16.to_le_bytes())?; // planes
+    out.write_all(&32u16.to_le_bytes())?; // bits per pixel
+    out.write_all(&3u32.to_le_bytes())?; // BI_BITFIELDS
+    out.write_all(&image_size.to_le_bytes())?;
+    out.write_all(&2835i32.to_le_bytes())?; // 72 DPI
+    out.write_all(&2835i32.to_le_bytes())?;
+    out.write_all(&0u32.to_le_bytes())?; // colors used
+    out.write_all(&0u32.to_le_bytes())?; // colors important
+    out.write_all(&0x00ff_0000u32.to_le_bytes())?; // red mask
+    out.write_all(&0x0000_ff00u32.to_le_bytes())?; // green mask
+    out.write_all(&0x0000_00ffu32.to_le_bytes())?; // blue mask
+    out.write_all(&0xff00_0000u32.to_le_bytes())?; // alpha mask
+    out.write_all(b"BGRs")?; // LCS_sRGB, little-endian
+    out.write_all(&[0; 36 + 12])?; // endpoints and gamma, unused for sRGB
+
+    let (stride, buf) = (img.stride(), img.buf());
+    let mut line = Vec::with_capacity(img.width() * 4);
+    for y in (0..img.height()).rev() {
+        let row = &buf[y * stride..y * stride + img.width()];
+        line.clear();
+        line.extend(row.iter().flat_map(|px| [px.b, px.g, px.r, 255]));
+        out.write_all(&line)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PX: BGRA8 = BGRA8 {
+        b: 1,
+        g: 2,
+        r: 3,
+        a: 0,
+    };
+
+    #[test]
+    fn pixels() {
+        let data = [PX; 2];
+        let img = ImgRef::new(&data, 2, 1);
+        let mut out = Vec::new();
+        write_pixels(&mut out, img, PixelFormat::Rgba8).unwrap();
+        assert_eq!(out, [3, 2, 1, 0, 3, 2, 1, 0]);
+
+        out.clear();
+        write_pixels(&mut out, img, PixelFormat::Bgr8).unwrap();
+        assert_eq!(out, [1, 2, 3, 1, 2, 3]);
+    }
+
+    #[test]
+    fn ppm() {
+        let data = [PX; 2];
+        let mut out = Vec::new();
+        write_ppm(&mut out, ImgRef::new(&data, 2, 1)).unwrap();
+        assert_eq!(out, b"P6\n2 1\n255\n\x03\x02\x01\x03\x02\x01");
+    }
+
+    #[cfg(feature = "scrot")]
+    #[test]
+    fn scrot_path() {
+        let time = chrono::NaiveDate::from_ymd_opt(2024, 3, 7)
+            .and_then(|d| d.and_hms_opt(9, 5, 0))
+            .unwrap();
+        assert_eq!(
+            expand_scrot_path(Path::new("/tmp/%Y-%m-%d_%H%M%S_%%_%x.png"), &time),
+            Path::new("/tmp/2024-03-07_090500_%_%x.png")
+        );
+    }
+
+    #[test]
+    fn bmp() {
+        let data = [PX; 6];
+        let mut out = Vec::new();
+        write_bmp(&mut out, ImgRef::new(&data, 3, 2)).unwrap();
+        assert_eq!(out.len(), 122 + 3 * 2 * 4);
+        assert_eq!(&out[..2], b"BM");
+        assert_eq!(out[2..6], 146u32.to_le_bytes());
+        assert_eq!(out[10..14], 122u32.to_le_bytes());
+        assert_eq!(out[122..126], [1, 2, 3, 255]);
+    }
+}
@@ -9,16 +9,76 @@ use rayon::prelude::*;
 use rgb::alt::BGRA8;
 use rgb::ColorComponentMap;
 
+use crate::types::ClipRect;
+
 const MASK_THRESHOLD: u8 = 127;
 
+/// Convert premultiplied alpha to straight alpha, which is what `compose` expects.
+/// Fully transparent and fully opaque pixels are the same in both representations.
+pub fn unpremultiply(icon: &mut [BGRA8]) {
+    for px in icon.iter_mut().filter(|px| px.a > 0 && px.a < 255) {
+        let a = u16::from(px.a);
+        let unmul = |c: u8| ((u16::from(c) * 255 + a / 2) / a).min(255) as u8;
+        px.b = unmul(px.b);
+        px.g = unmul(px.g);
+        px.r = unmul(px.r);
+    }
+}
+
+/// Make every pixel of `icon` fully opaque if its alpha is at least `threshold`,
+/// or fully transparent otherwise, for sharp edges with both `compose` and `invert`.
+pub fn threshold_alpha(icon: &mut [BGRA8], threshold: u8) {
+    for px in icon.iter_mut() {
+        px.a = if px.a >= threshold { 255 } else { 0 };
+    }
+}
+
+/// Multiply the color of every pixel of `icon` by `color`, scaled to 0-1. Alpha is kept,
+/// so a white or grayscale icon takes on `color`.
+pub fn colorize(icon: &mut [BGRA8], (r, g, b): (u8, u8, u8)) {
+    let tint = |c: u8, t: u8| ((u16::from(c) * u16::from(t) + 127) / 255) as u8;
+    for px in icon.iter_mut() {
+        px.r = tint(px.r, r);
+        px.g = tint(px.g, g);
+        px.b = tint(px.b, b);
+    }
+}
+
+/// The part of `top` placed at `x`,`y` that falls within `bot` and `clip`, relative to `top`.
+fn visible<T>(
+    bot: &ImgRefMut<T>,
+    top: &ImgRef<T>,
+    x: usize,
+    y: usize,
+    clip: Option<ClipRect>,
+) -> Option<ClipRect> {
+    let bounds = ClipRect::new(0, 0, bot.width(), bot.height());
+    let placed = ClipRect::new(x, y, top.width(), top.height());
+    let shown = placed
+        .intersect(&bounds)?
+        .intersect(&clip.unwrap_or(bounds))?;
+    Some(ClipRect::new(
+        shown.x - x,
+        shown.y - y,
+        shown.width,
+        shown.height,
+    ))
+}
+
+/// Nothing is drawn outside of `clip`.
+/// Parts of `top` or `mask` that fall off the image are always clipped.
 pub trait Compose {
-    fn compose(&mut self, top: ImgRef<BGRA8>, x: usize, y: usize);
-    fn invert(&mut self, mask: Option<ImgRef<BGRA8>>, x: usize, y: usize);
+    fn compose(&mut self, top: ImgRef<BGRA8>, x: usize, y: usize, clip: Option<ClipRect>);
+    fn invert(&mut self, mask: Option<ImgRef<BGRA8>>, x: usize, y: usize, clip: Option<ClipRect>);
 }
 
 impl Compose for ImgRefMut<'_, BGRA8> {
-    fn compose(&mut self, top: ImgRef<BGRA8>, x: usize, y: usize) {
-        let mut bot = self.sub_image_mut(x, y, top.width(), top.height());
+    fn compose(&mut self, top: ImgRef<BGRA8>, x: usize, y: usize, clip: Option<ClipRect>) {
+        let Some(part) = visible(self, &top, x, y, clip) else {
+            return;
+        };
+        let top = top.sub_image(part.x, part.y, part.width, part.height);
+        let mut bot = self.sub_image_mut(x + part.x, y + part.y, part.width, part.height);
 
         #[cfg(not(feature = "threads"))]
         for (bot_px, top_px) in bot
@@ -64,9 +124,13 @@ impl Compose for ImgRefMut<'_, BGRA8> {
             });
     }
 
-    fn invert(&mut self, mask: Option<ImgRef<BGRA8>>, x: usize, y: usize) {
+    fn invert(&mut self, mask: Option<ImgRef<BGRA8>>, x: usize, y: usize, clip: Option<ClipRect>) {
         if let Some(m) = mask {
-            let mut view = self.sub_image_mut(x, y, m.width(), m.height());
+            let Some(part) = visible(self, &m, x, y, clip) else {
+                return;
+            };
+            let m = m.sub_image(part.x, part.y, part.width, part.height);
+            let mut view = self.sub_image_mut(x + part.x, y + part.y, part.width, part.height);
 
             #[cfg(not(feature = "threads"))]
             for (view_px, _) in view
@@ -104,3 +168,107 @@ impl Compose for ImgRefMut<'_, BGRA8> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clip() {
+        let mut data = [BGRA8::default(); 4 * 4];
+        let bot = ImgRefMut::new(&mut data, 4, 4);
+        let icon = [BGRA8::default(); 2 * 2];
+        let top = ImgRef::new(&icon, 2, 2);
+        assert_eq!(
+            visible(&bot, &top, 1, 1, None),
+            Some(ClipRect::new(0, 0, 2, 2))
+        );
+        assert_eq!(
+            visible(&bot, &top, 3, 3, None),
+            Some(ClipRect::new(0, 0, 1, 1))
+        );
+        assert_eq!(visible(&bot, &top, 4, 0, None), None);
+        assert_eq!(
+            visible(&bot, &top, 1, 1, Some(ClipRect::new(2, 0, 2, 4))),
+            Some(ClipRect::new(1, 0, 1, 2))
+        );
+    }
+
+    #[test]
+    fn unpremultiply() {
+        let mut px = [
+            BGRA8 {
+                b: 64,
+                g: 32,
+                r: 128,
+                a: 128,
+            },
+            BGRA8 {
+                b: 7,
+                g: 7,
+                r: 7,
+                a: 0,
+            },
+        ];
+        super::unpremultiply(&mut px);
+        assert_eq!(
+            px[0],
+            BGRA8 {
+                b: 128,
+                g: 64,
+                r: 255,
+                a: 128,
+            }
+        );
+        assert_eq!(px[1].b, 7);
+    }
+
+    #[test]
+    fn threshold_alpha() {
+        let mut px = [0, 127, 128, 200, 255].map(|a| BGRA8 {
+            b: 10,
+            g: 20,
+            r: 30,
+            a,
+        });
+        super::threshold_alpha(&mut px, 128);
+        assert_eq!(px.map(|px| px.a), [0, 0, 255, 255, 255]);
+        assert_eq!(px[2].r, 30);
+    }
+
+    #[test]
+    fn colorize() {
+        let mut px = [
+            BGRA8 {
+                b: 255,
+                g: 255,
+                r: 255,
+                a: 100,
+            },
+            BGRA8 {
+                b: 128,
+                g: 128,
+                r: 128,
+                a: 255,
+            },
+        ];
+        super::colorize(&mut px, (255, 0, 128));
+        assert_eq!(
+            px,
+            [
+                BGRA8 {
+                    b: 128,
+                    g: 0,
+                    r: 255,
+                    a: 100,
+                },
+                BGRA8 {
+                    b: 64,
+                    g: 0,
+                    r: 128,
+                    a: 255,
+                },
+            ]
+        );
+    }
+}
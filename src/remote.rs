@@ -0,0 +1,109 @@
+use std::collections::hash_map::DefaultHasher;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Duration;
+
+use crate::util::TempFile;
+
+/// Refuse to download icons larger than this.
+const MAX_SIZE: u64 = 32 * 1024 * 1024;
+
+/// Give up on a stalled server quickly, locking shouldn't wait on the icon.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+pub fn is_url(s: &str) -> bool {
+    s.starts_with("http://") || s.starts_with("https://")
+}
+
+/// Fetch `url`, reusing the copy in `$XDG_CACHE_HOME/i3lockr` if it is younger than `ttl`.
+pub fn fetch(url: &str, ttl: Duration) -> Result<Vec<u8>, Box<dyn Error>> {
+    let cached = cache_path(url);
+
+    if let Some(ref path) = cached {
+        let fresh = fs::metadata(path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.elapsed().ok())
+            .is_some_and(|age| age < ttl);
+        if fresh {
+            return Ok(fs::read(path)?);
+        }
+    }
+
+    let bytes = match download(url) {
+        Ok(bytes) => bytes,
+        // a stale icon is better than no icon
+        Err(e) => match cached.as_ref().and_then(|p| fs::read(p).ok()) {
+            Some(bytes) => return Ok(bytes),
+            None => return Err(e),
+        },
+    };
+
+    if let Some(path) = cached {
+        // failing to cache shouldn't stop us from locking
+        let _ = store(&path, &bytes);
+    }
+
+    Ok(bytes)
+}
+
+fn download(url: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let agent = ureq::AgentBuilder::new()
+        .timeout_connect(TIMEOUT)
+        .timeout_read(TIMEOUT)
+        .build();
+    let mut bytes = Vec::new();
+    agent
+        .get(url)
+        .call()?
+        .into_reader()
+        .take(MAX_SIZE + 1)
+        .read_to_end(&mut bytes)?;
+    // a truncated icon would only fail to decode later, and must never reach the cache
+    if bytes.len() as u64 > MAX_SIZE {
+        return Err(format!("{} is larger than {} bytes", url, MAX_SIZE).into());
+    }
+    Ok(bytes)
+}
+
+/// Write through a temp file, so a half written icon never ends up in the cache.
+fn store(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let (tmp, mut file) = TempFile::create(path.with_extension(format!("{}.part", process::id())))?;
+    file.write_all(bytes)?;
+    tmp.persist(path)
+}
+
+fn cache_path(url: &str) -> Option<PathBuf> {
+    let dir = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".cache")))?;
+
+    let mut hasher = DefaultHasher::new();
+    url.hash(&mut hasher);
+
+    Some(
+        dir.join(env!("CARGO_PKG_NAME"))
+            .join(format!("{:016x}.png", hasher.finish())),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn url() {
+        assert!(is_url("https://example.com/logo.png"));
+        assert!(is_url("http://example.com/logo.png"));
+        assert!(!is_url("/usr/share/icons/logo.png"));
+        assert!(!is_url("httpsfile.png"));
+    }
+}
@@ -1,7 +1,7 @@
 use std::num::NonZeroUsize;
 
 use imgref::ImgExt;
-use imgref::ImgRefMut;
+use imgref::{ImgRef, ImgRefMut, ImgVec};
 
 use itertools::iproduct;
 
@@ -34,6 +34,14 @@ impl<T: Copy> Scale for ImgRefMut<'_, T> {
     }
 }
 
+/// Nearest-neighbor resize into a new buffer. `width` and `height` must be non-zero.
+pub fn resize<T: Copy>(img: ImgRef<T>, width: usize, height: usize) -> ImgVec<T> {
+    let buf = iproduct!(0..height, 0..width)
+        .map(|(y, x)| img[(x * img.width() / width, y * img.height() / height)])
+        .collect();
+    ImgVec::new(buf, width, height)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -63,4 +71,13 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn resize() {
+        let data = [RED, GREEN, BLUE, RED];
+        let img = super::resize(ImgRef::new(&data, 2, 2), 4, 2);
+        assert_eq!(img.buf(), &[RED, RED, GREEN, GREEN, BLUE, BLUE, RED, RED]);
+        let img = super::resize(img.as_ref(), 1, 1);
+        assert_eq!(img.buf(), &[RED]);
+    }
 }
@@ -0,0 +1,72 @@
+use imgref::ImgVec;
+
+use rgb::alt::BGRA8;
+
+use crate::cli::Checkerboard;
+
+/// A `width`x`height` checkerboard, starting with the first color in the top left.
+pub fn checkerboard(width: usize, height: usize, pattern: Checkerboard) -> ImgVec<BGRA8> {
+    let [first, second] = pattern.colors.map(|(r, g, b)| BGRA8 { b, g, r, a: 255 });
+    let size = pattern.size.get();
+
+    let buf = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            if (x / size + y / size) % 2 == 0 {
+                first
+            } else {
+                second
+            }
+        })
+        .collect();
+
+    ImgVec::new(buf, width, height)
+}
+
+/// A `width`x`height` gradient, blue grows to the right, green downwards and red diagonally.
+pub fn gradient(width: usize, height: usize) -> ImgVec<BGRA8> {
+    let buf = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| BGRA8 {
+            b: (x * 4) as u8,
+            g: (y * 4) as u8,
+            r: ((x + y) * 2) as u8,
+            a: 255,
+        })
+        .collect();
+
+    ImgVec::new(buf, width, height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checkerboard() {
+        let pattern: Checkerboard = "2:ffffff:000000".parse().unwrap();
+        let img = super::checkerboard(5, 3, pattern);
+        let white = BGRA8 {
+            b: 255,
+            g: 255,
+            r: 255,
+            a: 255,
+        };
+        let black = BGRA8 {
+            b: 0,
+            g: 0,
+            r: 0,
+            a: 255,
+        };
+        assert_eq!(img[(0, 0)], white);
+        assert_eq!(img[(1, 1)], white);
+        assert_eq!(img[(2, 0)], black);
+        assert_eq!(img[(4, 0)], white);
+        assert_eq!(img[(0, 2)], black);
+        assert_eq!(img[(2, 2)], white);
+
+        assert!("0:ffffff:000000".parse::<Checkerboard>().is_err());
+        assert!("8:fffff:000000".parse::<Checkerboard>().is_err());
+        assert!("8:ffffff".parse::<Checkerboard>().is_err());
+    }
+}
@@ -0,0 +1,114 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use fontdue::{Font, FontSettings};
+
+use imgref::{ImgRef, ImgRefMut, ImgVec};
+
+use rgb::alt::BGRA8;
+
+use crate::overlay::Compose;
+use crate::types::ClipRect;
+
+/// Load the font at `path`, or fontconfig's default sans-serif font.
+pub fn load_font(path: Option<&Path>) -> Result<Font, Box<dyn Error>> {
+    let path = match path {
+        Some(path) => path.to_path_buf(),
+        None => default_font()?,
+    };
+    let bytes = fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let font = Font::from_bytes(bytes, FontSettings::default())
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(font)
+}
+
+fn default_font() -> Result<PathBuf, Box<dyn Error>> {
+    let out = Command::new("fc-match")
+        .args(["--format=%{file}", "sans-serif"])
+        .output()
+        .map_err(|e| format!("Couldn't run fc-match to find a font: {}", e))?;
+    let path = String::from_utf8(out.stdout)?;
+    if !out.status.success() || path.is_empty() {
+        return Err("fc-match didn't find a font, pass one with --watermark-font".into());
+    }
+    Ok(PathBuf::from(path))
+}
+
+/// `text` on a single line of `size` pixels, in `color` on a transparent background.
+/// `color.a` scales the glyphs' coverage.
+pub fn render_line(font: &Font, text: &str, size: f32, color: BGRA8) -> ImgVec<BGRA8> {
+    let (ascent, descent) = font
+        .horizontal_line_metrics(size)
+        .map_or((size, 0.0), |m| (m.ascent, -m.descent));
+    let glyphs: Vec<_> = text.chars().map(|c| font.rasterize(c, size)).collect();
+
+    let advance: f32 = glyphs.iter().map(|(m, _)| m.advance_width).sum();
+    let width = advance.ceil().max(1.0) as usize;
+    let height = (ascent + descent).ceil().max(1.0) as usize;
+    let baseline = ascent.round() as i32;
+
+    let mut img = ImgVec::new(vec![BGRA8 { a: 0, ..color }; width * height], width, height);
+    let mut pen = 0.0_f32;
+    for (metrics, coverage) in &glyphs {
+        // ymin is how far the bitmap's bottom edge sits above the baseline
+        let left = pen.round() as i32 + metrics.xmin;
+        let top = baseline - metrics.ymin - metrics.height as i32;
+        for (i, &c) in coverage.iter().enumerate() {
+            let x = left + (i % metrics.width) as i32;
+            let y = top + (i / metrics.width) as i32;
+            if (0..width as i32).contains(&x) && (0..height as i32).contains(&y) {
+                let px = &mut img[(x as usize, y as usize)];
+                px.a =
+                    px.a.max(((u16::from(c) * u16::from(color.a) + 127) / 255) as u8);
+            }
+        }
+        pen += metrics.advance_width;
+    }
+    img
+}
+
+/// Repeat `mark` over `rect` of `img` in rows, every other row shifted by half a mark.
+pub fn tile(img: &mut ImgRefMut<BGRA8>, mark: ImgRef<BGRA8>, rect: ClipRect) {
+    let (step_x, step_y) = (mark.width().max(1), mark.height().max(1));
+    for (row, y) in (rect.y..rect.y + rect.height).step_by(step_y).enumerate() {
+        let shift = if row % 2 == 1 { step_x / 2 } else { 0 };
+        for x in (rect.x + shift..rect.x + rect.width).step_by(step_x) {
+            img.compose(mark, x, y, Some(rect));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: BGRA8 = BGRA8 {
+        b: 0,
+        g: 0,
+        r: 0,
+        a: 255,
+    };
+    const WHITE: BGRA8 = BGRA8 {
+        b: 255,
+        g: 255,
+        r: 255,
+        a: 255,
+    };
+
+    #[test]
+    fn tile() {
+        let mut data = [BLACK; 3 * 3];
+        let mark = [WHITE];
+        super::tile(
+            &mut ImgRefMut::new(&mut data, 3, 3),
+            ImgRef::new(&mark, 1, 1),
+            ClipRect::new(1, 1, 2, 2),
+        );
+        assert_eq!(
+            data,
+            [BLACK, BLACK, BLACK, BLACK, WHITE, WHITE, BLACK, WHITE, WHITE]
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use imgref::{ImgRef, ImgVec};
+
+use rgb::alt::BGRA8;
+
+/// Rotate `img` clockwise by `degrees`, sampling bilinearly.
+/// The result is just big enough to hold the rotated image, the corners are transparent.
+pub fn rotate(img: ImgRef<BGRA8>, degrees: f32) -> ImgVec<BGRA8> {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let (w, h) = (img.width() as f32, img.height() as f32);
+
+    // shave off float error so that right angles don't grow by a pixel
+    let new_w = ((w * cos.abs() + h * sin.abs()) - 1e-3).ceil().max(1.0) as usize;
+    let new_h = ((w * sin.abs() + h * cos.abs()) - 1e-3).ceil().max(1.0) as usize;
+    let (new_cx, new_cy) = (new_w as f32 / 2.0, new_h as f32 / 2.0);
+
+    let buf = (0..new_h)
+        .flat_map(|y| (0..new_w).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            // rotate each destination pixel's center back into the source
+            let dx = x as f32 + 0.5 - new_cx;
+            let dy = y as f32 + 0.5 - new_cy;
+            let sx = cos * dx + sin * dy + w / 2.0 - 0.5;
+            let sy = cos * dy - sin * dx + h / 2.0 - 0.5;
+            sample(img, sx, sy)
+        })
+        .collect();
+
+    ImgVec::new(buf, new_w, new_h)
+}
+
+/// Bilinear resize into a new buffer. `width` and `height` must be non-zero.
+pub fn resize(img: ImgRef<BGRA8>, width: usize, height: usize) -> ImgVec<BGRA8> {
+    let (sx, sy) = (
+        img.width() as f32 / width as f32,
+        img.height() as f32 / height as f32,
+    );
+    let (max_x, max_y) = ((img.width() - 1) as f32, (img.height() - 1) as f32);
+
+    let buf = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            // clamped so that the edges stay opaque
+            let src_x = ((x as f32 + 0.5) * sx - 0.5).clamp(0.0, max_x);
+            let src_y = ((y as f32 + 0.5) * sy - 0.5).clamp(0.0, max_y);
+            sample(img, src_x, src_y)
+        })
+        .collect();
+
+    ImgVec::new(buf, width, height)
+}
+
+/// Move `img` right by `dx` and down by `dy`, both in `0.0..1.0`, sampling bilinearly.
+/// The result grows by a pixel in each direction that was shifted.
+pub fn shift(img: ImgRef<BGRA8>, dx: f32, dy: f32) -> ImgVec<BGRA8> {
+    let new_w = img.width() + usize::from(dx > 0.0);
+    let new_h = img.height() + usize::from(dy > 0.0);
+
+    let buf = (0..new_h)
+        .flat_map(|y| (0..new_w).map(move |x| (x, y)))
+        .map(|(x, y)| sample(img, x as f32 - dx, y as f32 - dy))
+        .collect();
+
+    ImgVec::new(buf, new_w, new_h)
+}
+
+/// Bilinear sample at `x`,`y` in pixel coordinates, anything outside of `img` is transparent.
+fn sample(img: ImgRef<BGRA8>, x: f32, y: f32) -> BGRA8 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+
+    // weight colors by alpha so transparent pixels don't bleed their color in
+    let (mut b, mut g, mut r, mut a) = (0.0, 0.0, 0.0, 0.0);
+    for (oy, ox, weight) in [
+        (0, 0, (1.0 - fx) * (1.0 - fy)),
+        (0, 1, fx * (1.0 - fy)),
+        (1, 0, (1.0 - fx) * fy),
+        (1, 1, fx * fy),
+    ] {
+        let (px, py) = (x0 as isize + ox, y0 as isize + oy);
+        if px < 0 || py < 0 || px as usize >= img.width() || py as usize >= img.height() {
+            continue;
+        }
+        let p = img[(px as usize, py as usize)];
+        let weight = weight * f32::from(p.a);
+        b += f32::from(p.b) * weight;
+        g += f32::from(p.g) * weight;
+        r += f32::from(p.r) * weight;
+        a += weight;
+    }
+
+    if a == 0.0 {
+        return BGRA8::default();
+    }
+    BGRA8 {
+        b: (b / a).round() as u8,
+        g: (g / a).round() as u8,
+        r: (r / a).round() as u8,
+        a: a.round().min(255.0) as u8,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: BGRA8 = BGRA8 {
+        b: 0,
+        g: 0,
+        r: 255,
+        a: 255,
+    };
+    const BLUE: BGRA8 = BGRA8 {
+        b: 255,
+        g: 0,
+        r: 0,
+        a: 255,
+    };
+
+    #[test]
+    fn rotate() {
+        let data = [RED, BLUE];
+        let img = ImgRef::new(&data, 2, 1);
+
+        let same = super::rotate(img, 0.0);
+        assert_eq!(same.buf(), &data);
+
+        let right = super::rotate(img, 90.0);
+        assert_eq!((right.width(), right.height()), (1, 2));
+        assert_eq!(right.buf(), &[RED, BLUE]);
+
+        let left = super::rotate(img, -90.0);
+        assert_eq!(left.buf(), &[BLUE, RED]);
+
+        let diagonal = super::rotate(img, 45.0);
+        assert_eq!((diagonal.width(), diagonal.height()), (3, 3));
+        assert!(diagonal[(0, 0)].a < 64);
+    }
+
+    #[test]
+    fn resize() {
+        let data = [RED, BLUE];
+        let img = super::resize(ImgRef::new(&data, 2, 1), 4, 1);
+        assert_eq!(img[(0, 0)], RED);
+        assert_eq!(img[(3, 0)], BLUE);
+        assert!(img[(1, 0)].r > img[(1, 0)].b);
+        assert!(img.buf().iter().all(|p| p.a == 255));
+
+        let img = super::resize(img.as_ref(), 1, 1);
+        assert_eq!(img[(0, 0)].a, 255);
+    }
+
+    #[test]
+    fn shift() {
+        let data = [RED, BLUE];
+        let img = ImgRef::new(&data, 2, 1);
+
+        let same = super::shift(img, 0.0, 0.0);
+        assert_eq!(same.buf(), &data);
+
+        let half = super::shift(img, 0.5, 0.0);
+        assert_eq!((half.width(), half.height()), (3, 1));
+        assert_eq!(half[(0, 0)], BGRA8 { a: 128, ..RED });
+        assert_eq!(half[(1, 0)].a, 255);
+        assert_eq!(half[(2, 0)], BGRA8 { a: 128, ..BLUE });
+
+        let down = super::shift(img, 0.0, 0.25);
+        assert_eq!((down.width(), down.height()), (2, 2));
+        assert_eq!(down[(0, 0)].a, 191);
+        assert_eq!(down[(1, 1)], BGRA8 { a: 64, ..BLUE });
+    }
+}
@@ -0,0 +1,110 @@
+/// A rectangle of pixels, such as the part of the screenshot a monitor covers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClipRect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl ClipRect {
+    pub const fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub const fn area(&self) -> usize {
+        self.width * self.height
+    }
+
+    /// Whether `other` lies entirely within this rectangle.
+    pub const fn contains(&self, other: &Self) -> bool {
+        other.x >= self.x
+            && other.y >= self.y
+            && other.x + other.width <= self.x + self.width
+            && other.y + other.height <= self.y + self.height
+    }
+
+    /// The pixels both rectangles cover, if there are any.
+    pub fn intersect(&self, other: &Self) -> Option<Self> {
+        let left = self.x.max(other.x);
+        let up = self.y.max(other.y);
+        let right = (self.x + self.width).min(other.x + other.width);
+        let down = (self.y + self.height).min(other.y + other.height);
+        (left < right && up < down).then(|| Self::new(left, up, right - left, down - up))
+    }
+
+    /// How many pixels both rectangles cover.
+    pub fn overlap_area(&self, other: &Self) -> usize {
+        self.intersect(other).map_or(0, |r| r.area())
+    }
+
+    /// How much of this rectangle `other` covers, in percent.
+    pub fn overlap_percentage(&self, other: &Self) -> f32 {
+        match self.area() {
+            0 => 0.0,
+            area => self.overlap_area(other) as f32 * 100.0 / area as f32,
+        }
+    }
+
+    /// A rectangle of at most `width`x`height` in the middle of this one.
+    pub fn centered(&self, width: usize, height: usize) -> Self {
+        let (width, height) = (width.min(self.width), height.min(self.height));
+        Self::new(
+            self.x + (self.width - width) / 2,
+            self.y + (self.height - height) / 2,
+            width,
+            height,
+        )
+    }
+
+    /// Whether the rectangles share any pixels.
+    pub fn overlaps(&self, other: &Self) -> bool {
+        self.intersect(other).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intersect() {
+        let a = ClipRect::new(0, 0, 4, 4);
+        let b = ClipRect::new(2, 3, 4, 4);
+        assert_eq!(a.intersect(&b), Some(ClipRect::new(2, 3, 2, 1)));
+        assert_eq!(a.intersect(&ClipRect::new(4, 0, 1, 1)), None);
+        assert!(!a.overlaps(&ClipRect::new(0, 4, 4, 4)));
+    }
+
+    #[test]
+    fn overlap() {
+        let a = ClipRect::new(0, 0, 4, 4);
+        let b = ClipRect::new(2, 0, 4, 2);
+        assert_eq!(a.overlap_area(&b), 4);
+        assert_eq!(a.overlap_percentage(&b), 25.0);
+        assert_eq!(b.overlap_percentage(&a), 50.0);
+        assert_eq!(a.overlap_percentage(&ClipRect::new(4, 4, 1, 1)), 0.0);
+    }
+
+    #[test]
+    fn centered() {
+        let a = ClipRect::new(10, 0, 100, 50);
+        assert_eq!(a.centered(20, 10), ClipRect::new(50, 20, 20, 10));
+        assert_eq!(a.centered(200, 10), ClipRect::new(10, 20, 100, 10));
+    }
+
+    #[test]
+    fn contains() {
+        let a = ClipRect::new(1, 1, 4, 4);
+        assert!(a.contains(&ClipRect::new(1, 1, 4, 4)));
+        assert!(a.contains(&ClipRect::new(2, 2, 1, 1)));
+        assert!(!a.contains(&ClipRect::new(0, 1, 2, 2)));
+        assert!(!a.contains(&ClipRect::new(4, 4, 2, 1)));
+        assert_eq!(a.area(), 16);
+    }
+}
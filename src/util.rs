@@ -0,0 +1,212 @@
+use std::ffi::CString;
+#[cfg(feature = "remote-icon")]
+use std::fs::File;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering::SeqCst};
+use std::sync::Once;
+
+use libc::{c_char, c_int};
+
+/// How many files can be cleaned up by the signal handler at once.
+/// Any more are still removed on drop, just not when we're killed.
+const SLOTS: usize = 8;
+
+// the signal handler can't allocate or take locks, so paths are parked here as C strings
+static PATHS: [AtomicPtr<c_char>; SLOTS] = [const { AtomicPtr::new(ptr::null_mut()) }; SLOTS];
+static HANDLERS: Once = Once::new();
+
+/// A file that is removed when dropped, or when we get SIGINT or SIGTERM,
+/// unless it is `persist`ed first.
+#[cfg(feature = "remote-icon")]
+pub struct TempFile {
+    path: PathBuf,
+    slot: Option<usize>,
+}
+
+#[cfg(feature = "remote-icon")]
+impl TempFile {
+    /// Create a new, empty file at `path`, failing if it already exists.
+    pub fn create(path: PathBuf) -> io::Result<(Self, File)> {
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        let slot = register(&path);
+        Ok((Self { path, slot }, file))
+    }
+
+    /// Move the file to `to` and stop tracking it.
+    pub fn persist(mut self, to: &Path) -> io::Result<()> {
+        fs::rename(&self.path, to)?;
+        unregister(self.slot.take());
+        // nothing left to remove
+        self.path = PathBuf::new();
+        Ok(())
+    }
+}
+
+#[cfg(feature = "remote-icon")]
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        unregister(self.slot.take());
+        if !self.path.as_os_str().is_empty() {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// A file holding our PID that keeps a second i3lockr from running. Removed when dropped,
+/// or when we get SIGINT or SIGTERM.
+pub struct LockFile {
+    path: PathBuf,
+    slot: Option<usize>,
+}
+
+impl LockFile {
+    /// Take the lock at `path`, or `None` if a running process already holds it.
+    /// A lock left behind by a process that has died is taken over.
+    pub fn acquire(path: PathBuf) -> io::Result<Option<Self>> {
+        // a second try after clearing out a stale lock, someone else may have won the race
+        for _ in 0..2 {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    let slot = register(&path);
+                    let lock = Self { path, slot };
+                    write!(file, "{}", process::id())?;
+                    return Ok(Some(lock));
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    if holder_alive(&path) {
+                        return Ok(None);
+                    }
+                    if let Err(e) = fs::remove_file(&path) {
+                        if e.kind() != io::ErrorKind::NotFound {
+                            return Err(e);
+                        }
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Drop for LockFile {
+    fn drop(&mut self) {
+        unregister(self.slot.take());
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Whether the PID in the lock file at `path` belongs to a running process.
+fn holder_alive(path: &Path) -> bool {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|pid| pid.trim().parse::<u32>().ok())
+        .is_some_and(|pid| Path::new(&format!("/proc/{}/status", pid)).exists())
+}
+
+fn register(path: &Path) -> Option<usize> {
+    HANDLERS.call_once(|| unsafe {
+        libc::signal(libc::SIGINT, cleanup as libc::sighandler_t);
+        libc::signal(libc::SIGTERM, cleanup as libc::sighandler_t);
+    });
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?.into_raw();
+    let slot = PATHS.iter().position(|slot| {
+        slot.compare_exchange(ptr::null_mut(), c_path, SeqCst, SeqCst)
+            .is_ok()
+    });
+    if slot.is_none() {
+        // SAFETY: came from into_raw above and was never shared
+        drop(unsafe { CString::from_raw(c_path) });
+    }
+    slot
+}
+
+fn unregister(slot: Option<usize>) {
+    if let Some(i) = slot {
+        let c_path = PATHS[i].swap(ptr::null_mut(), SeqCst);
+        if !c_path.is_null() {
+            // SAFETY: only register puts pointers in the slots, always from into_raw
+            drop(unsafe { CString::from_raw(c_path) });
+        }
+    }
+}
+
+extern "C" fn cleanup(sig: c_int) {
+    for slot in &PATHS {
+        let c_path = slot.swap(ptr::null_mut(), SeqCst);
+        if !c_path.is_null() {
+            // leaks the string, but we're about to die anyway
+            unsafe { libc::unlink(c_path) };
+        }
+    }
+
+    // die the way we would have without the handler
+    unsafe {
+        libc::signal(sig, libc::SIG_DFL);
+        libc::raise(sig);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::sync::Mutex;
+
+    // both tests take signal handler slots
+    static SLOTS_LOCK: Mutex<()> = Mutex::new(());
+
+    #[test]
+    #[cfg(feature = "remote-icon")]
+    fn temp_file() {
+        let _guard = SLOTS_LOCK.lock().unwrap();
+        let dir = env::temp_dir();
+        let path = dir.join(format!("i3lockr-test-{}.part", process::id()));
+        let kept = dir.join(format!("i3lockr-test-{}.png", process::id()));
+
+        let (tmp, _) = TempFile::create(path.clone()).unwrap();
+        assert!(TempFile::create(path.clone()).is_err());
+        drop(tmp);
+        assert!(!path.exists());
+
+        let (tmp, _) = TempFile::create(path.clone()).unwrap();
+        tmp.persist(&kept).unwrap();
+        assert!(!path.exists());
+        assert!(kept.exists());
+        fs::remove_file(kept).unwrap();
+
+        assert!(PATHS.iter().all(|slot| slot.load(SeqCst).is_null()));
+    }
+
+    #[test]
+    fn lock_file() {
+        let _guard = SLOTS_LOCK.lock().unwrap();
+        let path = env::temp_dir().join(format!("i3lockr-test-{}.lock", process::id()));
+
+        let lock = LockFile::acquire(path.clone()).unwrap().unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            process::id().to_string()
+        );
+        assert!(LockFile::acquire(path.clone()).unwrap().is_none());
+        drop(lock);
+        assert!(!path.exists());
+
+        // no process has this PID, pid_max tops out at 2^22
+        fs::write(&path, "4294967295").unwrap();
+        let lock = LockFile::acquire(path.clone()).unwrap();
+        assert!(lock.is_some());
+        drop(lock);
+        assert!(!path.exists());
+    }
+}
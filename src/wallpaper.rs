@@ -0,0 +1,112 @@
+use std::error::Error;
+
+use imgref::ImgRef;
+
+use rgb::alt::BGRA8;
+use rgb::ComponentBytes;
+
+use xcb::{x, Connection, Xid};
+
+/// Set `img` as the root window's background.
+///
+/// The pixmap is kept around after we exit and advertised through `_XROOTPMAP_ID`
+/// and `ESETROOT_PMAP_ID`, like other wallpaper setters do, so that compositors and
+/// pseudo-transparent programs can find it. The previous wallpaper's pixmap is freed.
+pub fn set(img: ImgRef<BGRA8>) -> Result<(), Box<dyn Error>> {
+    let (conn, screen_num) = Connection::connect(None)?;
+    let setup = conn.get_setup();
+    let screen = setup
+        .roots()
+        .nth(screen_num as usize)
+        .ok_or("X server has no such screen")?;
+    let (root, depth) = (screen.root(), screen.root_depth());
+    let (width, height) = (img.width(), img.height());
+
+    let pixmap: x::Pixmap = conn.generate_id();
+    conn.send_request(&x::CreatePixmap {
+        depth,
+        pid: pixmap,
+        drawable: x::Drawable::Window(root),
+        width: width as u16,
+        height: height as u16,
+    });
+    let gc: x::Gcontext = conn.generate_id();
+    conn.send_request(&x::CreateGc {
+        cid: gc,
+        drawable: x::Drawable::Pixmap(pixmap),
+        value_list: &[],
+    });
+
+    // a whole screen is far bigger than a single request may be
+    let max_bytes = conn.get_maximum_request_length() as usize * 4 - 32;
+    let rows_per_request = (max_bytes / (width * 4)).max(1);
+    let pixels: Vec<BGRA8> = img.rows().flatten().copied().collect();
+    for (i, chunk) in pixels.chunks(rows_per_request * width).enumerate() {
+        conn.send_request(&x::PutImage {
+            format: x::ImageFormat::ZPixmap,
+            drawable: x::Drawable::Pixmap(pixmap),
+            gc,
+            width: width as u16,
+            height: (chunk.len() / width) as u16,
+            dst_x: 0,
+            dst_y: (i * rows_per_request) as i16,
+            left_pad: 0,
+            depth,
+            data: chunk.as_bytes(),
+        });
+    }
+    conn.send_request(&x::FreeGc { gc });
+
+    let xrootpmap = intern(&conn, b"_XROOTPMAP_ID")?;
+    let esetroot = intern(&conn, b"ESETROOT_PMAP_ID")?;
+
+    let cookie = conn.send_request(&x::GetProperty {
+        delete: false,
+        window: root,
+        property: esetroot,
+        r#type: x::ATOM_PIXMAP,
+        long_offset: 0,
+        long_length: 1,
+    });
+    if let Some(&old) = conn.wait_for_reply(cookie)?.value::<u32>().first() {
+        // the old pixmap was kept alive the same way, this frees it
+        conn.send_request(&x::KillClient { resource: old });
+    }
+
+    for property in [xrootpmap, esetroot] {
+        conn.send_request(&x::ChangeProperty {
+            mode: x::PropMode::Replace,
+            window: root,
+            property,
+            r#type: x::ATOM_PIXMAP,
+            data: &[pixmap.resource_id()],
+        });
+    }
+    conn.send_request(&x::ChangeWindowAttributes {
+        window: root,
+        value_list: &[x::Cw::BackPixmap(pixmap)],
+    });
+    conn.send_request(&x::ClearArea {
+        exposures: false,
+        window: root,
+        x: 0,
+        y: 0,
+        width: 0,
+        height: 0,
+    });
+
+    // otherwise the pixmap goes away with our connection
+    conn.send_and_check_request(&x::SetCloseDownMode {
+        mode: x::CloseDown::RetainPermanent,
+    })?;
+
+    Ok(())
+}
+
+fn intern(conn: &Connection, name: &[u8]) -> xcb::Result<x::Atom> {
+    let cookie = conn.send_request(&x::InternAtom {
+        only_if_exists: false,
+        name,
+    });
+    Ok(conn.wait_for_reply(cookie)?.atom())
+}
@@ -0,0 +1,112 @@
+use std::io;
+use std::process::Command;
+
+use crate::cli::MonitorOrder;
+use crate::monitor::Monitor;
+use crate::types::ClipRect;
+
+/// A connected output in the output of `xrandr --query`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Output {
+    pub name: String,
+    pub primary: bool,
+    pub rect: ClipRect,
+}
+
+/// The connected outputs in the output of `xrandr --query`.
+/// Connected outputs that aren't driving anything have no geometry and are skipped.
+pub fn parse_xrandr_output(stdout: &str) -> Vec<Output> {
+    stdout
+        .lines()
+        .filter(|line| line.contains(" connected"))
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            let name = words.next()?.to_owned();
+            let words: Vec<&str> = words.collect();
+            Some(Output {
+                name,
+                primary: words.contains(&"primary"),
+                rect: words.iter().copied().find_map(geometry)?,
+            })
+        })
+        .collect()
+}
+
+/// `WxH+X+Y`
+fn geometry(s: &str) -> Option<ClipRect> {
+    let (size, pos) = s.split_once('+')?;
+    let (w, h) = size.split_once('x')?;
+    let (x, y) = pos.split_once('+')?;
+    Some(ClipRect::new(
+        x.parse().ok()?,
+        y.parse().ok()?,
+        w.parse().ok()?,
+        h.parse().ok()?,
+    ))
+}
+
+/// Ask the `xrandr` binary for the monitor layout, for when RandR can't be queried directly.
+pub fn query(order: MonitorOrder) -> io::Result<Vec<Monitor>> {
+    let out = Command::new("xrandr").arg("--query").output()?;
+    if !out.status.success() {
+        return Err(io::Error::other(format!(
+            "xrandr failed: {}",
+            String::from_utf8_lossy(&out.stderr).trim()
+        )));
+    }
+
+    let mut outputs = parse_xrandr_output(&String::from_utf8_lossy(&out.stdout));
+    if order == MonitorOrder::Physical {
+        outputs.sort_by_key(|o| (o.rect.x, o.rect.y));
+    }
+
+    Ok(outputs
+        .into_iter()
+        .enumerate()
+        .map(|(index, o)| Monitor {
+            index,
+            name: o.name,
+            x: o.rect.x,
+            y: o.rect.y,
+            width: o.rect.width,
+            height: o.rect.height,
+            mm_width: 0,
+            mm_height: 0,
+            primary: o.primary,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let out = "\
+Screen 0: minimum 320 x 200, current 4480 x 1440, maximum 16384 x 16384
+DP-1 connected primary 2560x1440+1920+0 (normal left inverted right x axis y axis) 597mm x 336mm
+   2560x1440     59.95*+
+HDMI-1 connected 1920x1080+0+180 (normal left inverted right x axis y axis) 527mm x 296mm
+   1920x1080     60.00*+  50.00
+HDMI-2 disconnected (normal left inverted right x axis y axis)
+DP-2 connected (normal left inverted right x axis y axis)
+   1920x1080     60.00 +
+";
+        assert_eq!(
+            parse_xrandr_output(out),
+            [
+                Output {
+                    name: "DP-1".to_owned(),
+                    primary: true,
+                    rect: ClipRect::new(1920, 0, 2560, 1440),
+                },
+                Output {
+                    name: "HDMI-1".to_owned(),
+                    primary: false,
+                    rect: ClipRect::new(0, 180, 1920, 1080),
+                },
+            ]
+        );
+    }
+}
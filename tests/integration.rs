@@ -0,0 +1,134 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[cfg(feature = "png")]
+use imagefmt::ColFmt;
+
+/// A fresh temporary directory for the test `name`.
+fn test_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("i3lockr-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[cfg(feature = "png")]
+#[test]
+fn raw_input_to_png() {
+    let dir = test_dir("integration");
+    let input = dir.join("input.raw");
+    let output = dir.join("test_out.png");
+
+    // a gradient, so the blur has something to do
+    let (w, h) = (320, 240);
+    let raw: Vec<u8> = (0..w * h)
+        .flat_map(|i| {
+            let (x, y) = (i % w, i / w);
+            [(x % 256) as u8, (y % 256) as u8, ((x + y) % 256) as u8, 255]
+        })
+        .collect();
+    fs::write(&input, raw).unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_i3lockr"))
+        .args(["--raw-input"])
+        .arg(&input)
+        .args(["--raw-width", "320", "--raw-height", "240"])
+        .args(["--blur", "3", "--darken", "20", "--output-png"])
+        .arg(&output)
+        // keep the user's config file out of it
+        .env("XDG_CONFIG_HOME", &dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let png = imagefmt::read(&output, ColFmt::Auto).unwrap();
+    assert_eq!((png.w, png.h), (320, 240));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+/// What the mock i3lock was called with.
+struct Locked {
+    args: Vec<String>,
+    image: Vec<u8>,
+}
+
+/// Build tests/integration/mock_i3lock.rs into `dir` as `i3lock`.
+fn build_mock(dir: &Path) {
+    let status = Command::new("rustc")
+        .arg(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/integration/mock_i3lock.rs"))
+        .arg("-o")
+        .arg(dir.join("i3lock"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+/// Lock a `w`x`h` --raw-input filled with the BGRA `pixel` using the mock i3lock.
+fn lock_with_mock(name: &str, (w, h): (usize, usize), pixel: [u8; 4], args: &[&str]) -> Locked {
+    let dir = test_dir(name);
+    build_mock(&dir);
+    let input = dir.join("input.raw");
+    fs::write(&input, pixel.repeat(w * h)).unwrap();
+
+    let path = env::var_os("PATH").unwrap_or_default();
+    let path = env::join_paths(std::iter::once(dir.clone()).chain(env::split_paths(&path)));
+    let status = Command::new(env!("CARGO_BIN_EXE_i3lockr"))
+        .arg("--raw-input")
+        .arg(&input)
+        .args([
+            "--raw-width",
+            &w.to_string(),
+            "--raw-height",
+            &h.to_string(),
+        ])
+        .args(args)
+        // wait for the mock to read everything
+        .args(["--", "--nofork"])
+        .env("PATH", path.unwrap())
+        .env("MOCK_I3LOCK_DIR", &dir)
+        .env("XDG_CONFIG_HOME", &dir)
+        .env_remove("I3LOCKR_ICON")
+        .env_remove("IMAGE_PATH")
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let locked = Locked {
+        args: fs::read_to_string(dir.join("args"))
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect(),
+        image: fs::read(dir.join("image")).unwrap(),
+    };
+    fs::remove_dir_all(dir).unwrap();
+    locked
+}
+
+#[test]
+fn mock_raw_size() {
+    let locked = lock_with_mock("mock-raw", (64, 48), [10, 20, 30, 255], &[]);
+    assert!(locked.args.iter().any(|a| a == "--raw=64x48:native"));
+    assert_eq!(locked.image.len(), 64 * 48 * 4);
+    assert!(locked.image.chunks(4).all(|px| px[..3] == [10, 20, 30]));
+}
+
+#[test]
+fn mock_pixel_format() {
+    let args = ["--pixel-format", "rgb"];
+    let locked = lock_with_mock("mock-rgb", (64, 48), [10, 20, 30, 255], &args);
+    assert!(locked.args.iter().any(|a| a == "--raw=64x48:rgb"));
+    assert_eq!(locked.image.len(), 64 * 48 * 3);
+    assert!(locked.image.chunks(3).all(|px| px == [30, 20, 10]));
+}
+
+#[cfg(feature = "brightness")]
+#[test]
+fn mock_darken() {
+    let args = ["--darken", "20", "--channel", "r"];
+    let locked = lock_with_mock("mock-darken", (64, 48), [40, 60, 80, 255], &args);
+    assert_eq!(locked.image.len(), 64 * 48 * 4);
+    assert!(locked.image.chunks(4).all(|px| px[..3] == [40, 60, 60]));
+}
@@ -0,0 +1,27 @@
+//! Stand-in for i3lock, built by the integration tests with plain rustc.
+//!
+//! When given an image with `-i`, its arguments are written to `$MOCK_I3LOCK_DIR/args`,
+//! one per line, and whatever it reads from stdin to `$MOCK_I3LOCK_DIR/image`.
+//! Anything else is i3lockr probing for options or the version, which just succeeds.
+
+use std::env;
+use std::fs;
+use std::io::{self, Read};
+use std::path::PathBuf;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|a| a == "--version") {
+        eprintln!("i3lock: version 2.13 © 2010 Michael Stapelberg");
+        return;
+    }
+    if !args.iter().any(|a| a == "-i") {
+        return;
+    }
+
+    let dir = PathBuf::from(env::var_os("MOCK_I3LOCK_DIR").expect("MOCK_I3LOCK_DIR isn't set"));
+    let mut image = Vec::new();
+    io::stdin().read_to_end(&mut image).unwrap();
+    fs::write(dir.join("args"), args.join("\n")).unwrap();
+    fs::write(dir.join("image"), image).unwrap();
+}